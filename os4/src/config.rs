@@ -4,15 +4,127 @@ pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// size of a level-1 megapage
+pub const HUGE_PAGE_SIZE: usize = 0x20_0000;
+/// Hand out never-used frames before recycled ones, so frame numbers do not
+/// depend on the order frames were freed. Recycled frames then sit idle until
+/// the untouched part of memory runs out.
+pub const DETERMINISTIC_FRAMES: bool = false;
 pub const MAX_SYSCALL_NUM: usize = 500;
+/// Pages past which a range flush gives up on per-page `sfence.vma` and
+/// flushes the whole TLB once.
+pub const TLB_FLUSH_ALL_THRESHOLD: usize = 32;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// Kernel stack sizes that differ from `KERNEL_STACK_SIZE`, as
+/// `(app_id, size)` with `size` a multiple of `PAGE_SIZE`, e.g. `(0, 4096 * 2)`.
+pub const KERNEL_STACK_OVERRIDES: &[(usize, usize)] = &[];
+/// Kernel stack size of `app_id`.
+pub fn kernel_stack_size(app_id: usize) -> usize {
+    KERNEL_STACK_OVERRIDES
+        .iter()
+        .find(|(id, _)| *id == app_id)
+        .map_or(KERNEL_STACK_SIZE, |(_, size)| *size)
+}
+/// Every app gets a slot as large as the largest kernel stack, plus a guard page.
+const fn kernel_stack_slot_size() -> usize {
+    let mut max = KERNEL_STACK_SIZE;
+    let mut i = 0;
+    while i < KERNEL_STACK_OVERRIDES.len() {
+        if KERNEL_STACK_OVERRIDES[i].1 > max {
+            max = KERNEL_STACK_OVERRIDES[i].1;
+        }
+        i += 1;
+    }
+    max
+}
 /// Return (bottom, top) of a kernel stack in kernel space.
 pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
-    let bottom = top - KERNEL_STACK_SIZE;
+    let top = TRAMPOLINE - app_id * (kernel_stack_slot_size() + PAGE_SIZE);
+    let bottom = top - kernel_stack_size(app_id);
     (bottom, top)
 }
+/// Written at the lowest word of every kernel stack, checked on each trap.
+pub const KERNEL_STACK_CANARY: usize = 0x5aa5_c3d2_e1f0_0f1e;
 
 pub const CLOCK_FREQ: usize = 12500000;
+/// Bytes the console holds back until a newline, flushed early when full.
+pub const CONSOLE_BUFFER_SIZE: usize = 256;
+/// Index of the task started by `run_first_task`, e.g. a shell or init app.
+/// Counted among the apps that loaded, so it must be below their number.
+pub const FIRST_TASK_ID: usize = 0;
+/// Ids of the tasks picked by the first switches after the first task, in
+/// order, e.g. `&[2, 0, 1]` to force an interleaving. An entry whose task is
+/// not `Ready` when its turn comes is skipped; once the list is used up
+/// `SCHED_POLICY` takes over. Every id must be below the number of apps loaded.
+pub const BOOT_ORDER: &[usize] = &[];
+/// Total running time an app may use before it is killed, `None` for unlimited.
+pub const APP_TIME_BUDGET_MS: Option<usize> = None;
+pub const TIME_BUDGET_EXIT_CODE: i32 = -9;
+/// Exit code of an app killed on a page fault it cannot recover from.
+pub const PAGE_FAULT_EXIT_CODE: i32 = -2;
+/// Exit code of an app killed on an illegal instruction.
+pub const ILLEGAL_INSTRUCTION_EXIT_CODE: i32 = -3;
+/// Exit code of the run when the apps left are all blocked, a deadlock.
+pub const DEADLOCK_EXIT_CODE: i32 = -4;
+/// Let `munmap` take a length that ends mid-page. The end is then rounded
+/// down, so only pages lying wholly inside the request are freed. Off by
+/// default: such a request fails with `EINVAL`.
+pub const MUNMAP_PARTIAL_END: bool = false;
+/// Most fds a task can have open, the fd table never grows past it.
+pub const MAX_FD_NUM: usize = 16;
+/// Bytes a pipe holds before writers have to wait for a reader.
+pub const PIPE_BUFFER_SIZE: usize = 256;
+/// Timer preemptions without a syscall after which a task is reported as possibly hung.
+pub const PREEMPT_WARN_THRESHOLD: usize = 200;
+/// Timer ticks a task may hold off preemption for with `sys_preempt_disable`
+/// before it is preempted anyway and preemption is enabled again.
+pub const MAX_PREEMPT_DISABLE_TICKS: usize = 20;
+/// Timer ticks another task may wait `Ready` without any switch to a
+/// different task before the scheduler is considered stalled and forced to.
+pub const SCHED_WATCHDOG_TICKS: usize = 100;
+/// Timer ticks between two samples of the running task for `cpu_profile`.
+/// Keep it odd: with two tasks taking turns every tick, an even period
+/// samples the same one every time.
+pub const PROFILE_SAMPLE_TICKS: usize = 7;
+/// Tasks `cpu_profile` keeps a count for; samples of higher ids are dropped.
+pub const MAX_TASKS: usize = 64;
+
+/// What the panic handler does once the message is out.
+#[allow(unused)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PanicAction {
+    /// stop the hart with interrupts off, leaving the emulator running for
+    /// a debugger to attach
+    Halt,
+    /// power off, reporting a system failure so the emulator exits non-zero
+    Shutdown,
+    /// cold reboot back into the bootloader
+    Reboot,
+}
+
+/// Panic behavior, `Shutdown` so a test run that panics fails instead of hanging.
+pub const PANIC_ACTION: PanicAction = PanicAction::Shutdown;
+
+/// Scheduling policies that `find_next_task` can dispatch on.
+#[allow(unused)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SchedPolicy {
+    /// pick the next `Ready` task in task list order
+    RoundRobin,
+    /// pick the `Ready` task with the highest priority
+    Priority,
+    /// pick the `Ready` task with the smallest stride
+    Stride,
+    /// pick the `Ready` task with the nearest deadline, those without one last
+    Edf,
+}
+
+/// Scheduling policy, fixed at build time.
+pub const SCHED_POLICY: SchedPolicy = SchedPolicy::RoundRobin;
+pub const DEFAULT_PRIORITY: isize = 16;
+/// Most a `Ready` task's priority can be raised by aging under
+/// `SchedPolicy::Priority`; tasks within this much of each other all progress.
+pub const PRIORITY_AGING_CAP: isize = 64;
+pub const BIG_STRIDE: usize = 0x10_0000;
@@ -2,16 +2,61 @@
     本模块实现了 print 和 println 宏
 */
 
-use crate::sbi::console_putchar;
+use crate::config::CONSOLE_BUFFER_SIZE;
+use crate::sbi::{console_putchar, console_write};
 use core::fmt::{self, Write};
+use spin::Mutex;
+
+/// Bytes waiting for a newline before they go to the SBI in one batch.
+pub struct LineBuffer {
+    buf: [u8; CONSOLE_BUFFER_SIZE],
+    len: usize,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; CONSOLE_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+    /// Append `bytes`, handing the buffer to `sink` after every newline and
+    /// whenever it fills up.
+    pub fn write(&mut self, bytes: &[u8], sink: &mut impl FnMut(&[u8])) {
+        for &byte in bytes {
+            self.buf[self.len] = byte;
+            self.len += 1;
+            if byte == b'\n' || self.len == CONSOLE_BUFFER_SIZE {
+                self.flush(sink);
+            }
+        }
+    }
+    pub fn flush(&mut self, sink: &mut impl FnMut(&[u8])) {
+        if self.len > 0 {
+            sink(&self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+}
+
+static CONSOLE: Mutex<LineBuffer> = Mutex::new(LineBuffer::new());
 
 struct Stdout;
 
+/// Put `bytes` out through the line buffer, whether or not they are UTF-8.
+pub fn write_bytes(bytes: &[u8]) {
+    match CONSOLE.try_lock() {
+        Some(mut console) => console.write(bytes, &mut console_write),
+        // only when printing from a panic in the middle of a print. `bytes`
+        // may be anywhere, e.g. on a kernel stack, which is not identity
+        // mapped as `console_write` needs, so go a byte at a time
+        None => bytes.iter().for_each(|&c| console_putchar(c as usize)),
+    }
+}
+
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
-        }
+        write_bytes(s.as_bytes());
         Ok(())
     }
 }
@@ -20,6 +65,13 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Put out whatever is still waiting for a newline.
+pub fn flush() {
+    if let Some(mut console) = CONSOLE.try_lock() {
+        console.flush(&mut console_write);
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -33,3 +85,31 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
     }
 }
+
+#[allow(unused)]
+/// a large write comes out whole, in one batch per line or full buffer
+pub fn console_buffer_test() {
+    use crate::sbi::console_calls;
+    use alloc::vec::Vec;
+    let mut input = Vec::new();
+    for line in 0..20 {
+        input.extend((0..CONSOLE_BUFFER_SIZE / 4).map(|i| b'a' + ((line + i) % 26) as u8));
+        input.push(b'\n');
+    }
+    // the last line alone overflows the buffer
+    input.extend(core::iter::repeat(b'z').take(CONSOLE_BUFFER_SIZE + 10));
+    let mut buffer = LineBuffer::new();
+    let mut batches: Vec<Vec<u8>> = Vec::new();
+    buffer.write(&input, &mut |chunk| batches.push(chunk.to_vec()));
+    assert_eq!(batches.len(), 21);
+    buffer.flush(&mut |chunk| batches.push(chunk.to_vec()));
+    assert_eq!(batches.len(), 22);
+    assert_eq!(batches.concat(), input);
+    // through the real console it takes no more SBI calls than that
+    // with the debug console, and one per byte without
+    let line = [b'-'; 64];
+    let before = console_calls();
+    print!("{}\n", core::str::from_utf8(&line).unwrap());
+    assert!(console_calls() - before <= line.len() + 1);
+    info!("console_buffer_test passed!");
+}
@@ -0,0 +1,87 @@
+//! Just enough of a flattened device tree reader to find the end of RAM
+//!
+//! The SBI passes the physical address of the device tree blob in `a1`. It
+//! is read once at boot, before paging is on and before the frame allocator
+//! may hand out the frames it sits in.
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+fn be32(addr: usize) -> u32 {
+    u32::from_be(unsafe { (addr as *const u32).read_volatile() })
+}
+
+/// The nul-terminated string at `addr`, without the nul.
+fn c_str(addr: usize) -> &'static [u8] {
+    let mut len = 0;
+    while unsafe { *((addr + len) as *const u8) } != 0 {
+        len += 1;
+    }
+    unsafe { core::slice::from_raw_parts(addr as *const u8, len) }
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// A number `cells` 32-bit cells long, big endian, at `addr`.
+fn read_cells(addr: usize, cells: usize) -> usize {
+    (0..cells).fold(0, |value, i| (value << 32) | be32(addr + 4 * i) as usize)
+}
+
+/// The highest end of the ranges in the `reg` of the `memory` nodes of the
+/// device tree at `dtb`, `None` if there is no tree or no memory node in it.
+pub fn ram_end(dtb: usize) -> Option<usize> {
+    if dtb == 0 || dtb % 4 != 0 || be32(dtb) != FDT_MAGIC {
+        return None;
+    }
+    let strings = dtb + be32(dtb + 12) as usize;
+    let mut p = dtb + be32(dtb + 8) as usize;
+    let (mut address_cells, mut size_cells) = (2, 1);
+    let mut depth = 0;
+    let mut in_memory = false;
+    let mut end = None;
+    loop {
+        let token = be32(p);
+        p += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = c_str(p);
+                p += align4(name.len() + 1);
+                depth += 1;
+                in_memory = depth == 2 && name.starts_with(b"memory");
+            }
+            FDT_END_NODE => {
+                depth -= 1;
+                in_memory = false;
+            }
+            FDT_PROP => {
+                let len = be32(p) as usize;
+                let name = c_str(strings + be32(p + 4) as usize);
+                let value = p + 8;
+                p = value + align4(len);
+                match name {
+                    // the cells of the memory nodes are given by the root
+                    b"#address-cells" if depth == 1 => address_cells = be32(value) as usize,
+                    b"#size-cells" if depth == 1 => size_cells = be32(value) as usize,
+                    b"reg" if in_memory => {
+                        let entry = 4 * (address_cells + size_cells);
+                        for offset in (0..len / entry).map(|i| i * entry) {
+                            let base = read_cells(value + offset, address_cells);
+                            let size = read_cells(value + offset + 4 * address_cells, size_cells);
+                            end = end.max(Some(base + size));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => return end,
+            _ => return None,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! File descriptors
+//!
+//! Every task has a small fd table of [`FileDescriptor`]s. Behind them are
+//! the console and the ends of [`pipe`]s; `sys_read`, `sys_write` and
+//! `sys_close` go through the table so that real files can be added later.
+
+mod pipe;
+
+use crate::config::MAX_FD_NUM;
+use alloc::vec;
+use alloc::vec::Vec;
+use pipe::{pipe_close_end, pipe_open_end};
+pub use pipe::{pipe_create, pipe_exists, pipe_read, pipe_write};
+
+/// What an open fd of a task refers to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FileDescriptor {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// read end of the pipe with this id
+    PipeRead(usize),
+    /// write end of the pipe with this id
+    PipeWrite(usize),
+}
+
+impl FileDescriptor {
+    pub fn readable(&self) -> bool {
+        matches!(self, FileDescriptor::Stdin | FileDescriptor::PipeRead(_))
+    }
+    pub fn writable(&self) -> bool {
+        matches!(
+            self,
+            FileDescriptor::Stdout | FileDescriptor::Stderr | FileDescriptor::PipeWrite(_)
+        )
+    }
+    /// Account for a new fd referring to this file.
+    pub fn opened(&self) {
+        match *self {
+            FileDescriptor::PipeRead(id) => pipe_open_end(id, false),
+            FileDescriptor::PipeWrite(id) => pipe_open_end(id, true),
+            _ => {}
+        }
+    }
+    /// Account for an fd referring to this file being closed.
+    pub fn closed(&self) {
+        match *self {
+            FileDescriptor::PipeRead(id) => pipe_close_end(id, false),
+            FileDescriptor::PipeWrite(id) => pipe_close_end(id, true),
+            _ => {}
+        }
+    }
+}
+
+/// The fd table an app starts with: stdin, stdout and stderr at 0, 1 and 2.
+pub fn default_fd_table() -> Vec<Option<FileDescriptor>> {
+    vec![
+        Some(FileDescriptor::Stdin),
+        Some(FileDescriptor::Stdout),
+        Some(FileDescriptor::Stderr),
+    ]
+}
+
+/// Put `file` in the lowest free slot of `fd_table`, growing the table up to
+/// `MAX_FD_NUM` slots. Returns the new fd, `None` if the table is full.
+pub fn alloc_fd(fd_table: &mut Vec<Option<FileDescriptor>>, file: FileDescriptor) -> Option<usize> {
+    if let Some(fd) = fd_table.iter().position(|slot| slot.is_none()) {
+        fd_table[fd] = Some(file);
+        Some(fd)
+    } else if fd_table.len() < MAX_FD_NUM {
+        fd_table.push(Some(file));
+        Some(fd_table.len() - 1)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,132 @@
+//! Pipes between tasks
+//!
+//! A pipe is a fixed-size ring buffer with a read end and a write end. No
+//! task can fork yet, so instead of being inherited every pipe stays in a
+//! registry for as long as the kernel runs, and any task may open either end
+//! of it by id.
+
+use crate::config::PIPE_BUFFER_SIZE;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// A ring buffer with counts of the fds open on either end.
+pub struct Pipe {
+    buffer: [u8; PIPE_BUFFER_SIZE],
+    /// index of the oldest byte
+    head: usize,
+    /// bytes held, from `head` on
+    len: usize,
+    readers: usize,
+    writers: usize,
+}
+
+impl Pipe {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0; PIPE_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            readers: 0,
+            writers: 0,
+        }
+    }
+    /// Move the oldest bytes into `buf`, returning how many were moved.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let count = buf.len().min(self.len);
+        for byte in buf[..count].iter_mut() {
+            *byte = self.buffer[self.head];
+            self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        }
+        self.len -= count;
+        count
+    }
+    /// Append as much of `buf` as fits, returning how many bytes were taken.
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        let count = buf.len().min(PIPE_BUFFER_SIZE - self.len);
+        for &byte in &buf[..count] {
+            self.buffer[(self.head + self.len) % PIPE_BUFFER_SIZE] = byte;
+            self.len += 1;
+        }
+        count
+    }
+}
+
+lazy_static! {
+    /// every pipe ever created, indexed by id
+    static ref PIPES: UPSafeCell<Vec<Pipe>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// Create a pipe with no end open yet, returning its id.
+pub fn pipe_create() -> usize {
+    let mut pipes = PIPES.exclusive_access();
+    pipes.push(Pipe::new());
+    pipes.len() - 1
+}
+
+pub fn pipe_exists(id: usize) -> bool {
+    id < PIPES.exclusive_access().len()
+}
+
+/// Count one more fd open on the read or write end of pipe `id`.
+pub fn pipe_open_end(id: usize, write: bool) {
+    let pipe = &mut PIPES.exclusive_access()[id];
+    if write {
+        pipe.writers += 1;
+    } else {
+        pipe.readers += 1;
+    }
+}
+
+/// Count one fd less open on the read or write end of pipe `id`.
+pub fn pipe_close_end(id: usize, write: bool) {
+    let pipe = &mut PIPES.exclusive_access()[id];
+    if write {
+        pipe.writers -= 1;
+    } else {
+        pipe.readers -= 1;
+    }
+}
+
+/// Read from pipe `id` into `buf`. Returns `Some(0)` at end of file, when
+/// the pipe is empty and no write end is open, and `None` if the caller has
+/// to wait for a writer.
+pub fn pipe_read(id: usize, buf: &mut [u8]) -> Option<usize> {
+    let pipe = &mut PIPES.exclusive_access()[id];
+    match pipe.read(buf) {
+        0 if pipe.writers > 0 && !buf.is_empty() => None,
+        count => Some(count),
+    }
+}
+
+/// Write `buf` to pipe `id`. Returns `Some(0)` if no read end is open, and
+/// `None` if the caller has to wait for a reader to make room.
+pub fn pipe_write(id: usize, buf: &[u8]) -> Option<usize> {
+    let pipe = &mut PIPES.exclusive_access()[id];
+    if pipe.readers == 0 {
+        return Some(0);
+    }
+    match pipe.write(buf) {
+        0 if !buf.is_empty() => None,
+        count => Some(count),
+    }
+}
+
+#[allow(unused)]
+/// bytes come out in order across the wrap-around, and a full pipe takes no more
+pub fn pipe_ring_buffer_test() {
+    let mut pipe = Pipe::new();
+    let mut out = [0u8; PIPE_BUFFER_SIZE];
+    // move head near the end so the next writes wrap
+    assert_eq!(pipe.write(&[0; PIPE_BUFFER_SIZE - 3]), PIPE_BUFFER_SIZE - 3);
+    assert_eq!(pipe.read(&mut out), PIPE_BUFFER_SIZE - 3);
+    let data: Vec<u8> = (0..PIPE_BUFFER_SIZE + 10).map(|i| i as u8).collect();
+    assert_eq!(pipe.write(&data), PIPE_BUFFER_SIZE);
+    assert_eq!(pipe.write(&data), 0);
+    assert_eq!(pipe.read(&mut out[..5]), 5);
+    assert_eq!(&out[..5], &data[..5]);
+    assert_eq!(pipe.read(&mut out), PIPE_BUFFER_SIZE - 5);
+    assert_eq!(&out[..PIPE_BUFFER_SIZE - 5], &data[5..PIPE_BUFFER_SIZE]);
+    assert_eq!(pipe.read(&mut out), 0);
+    info!("pipe_ring_buffer_test passed!");
+}
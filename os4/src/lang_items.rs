@@ -1,4 +1,5 @@
-use crate::sbi::shutdown;
+use crate::config::{PanicAction, PANIC_ACTION};
+use crate::sbi::{reboot, shutdown_failure};
 use core::panic::PanicInfo;
 
 #[panic_handler]
@@ -13,5 +14,27 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("[kernel] Panicked: {}", info.message().unwrap());
     }
-    shutdown()
+    match PANIC_ACTION {
+        PanicAction::Halt => halt(),
+        PanicAction::Shutdown => shutdown_failure(),
+        PanicAction::Reboot => reboot(),
+    }
+}
+
+/// Park the hart for good, with interrupts off so only a debugger wakes it.
+fn halt() -> ! {
+    crate::console::flush();
+    unsafe {
+        riscv::register::sstatus::clear_sie();
+    }
+    loop {
+        unsafe { core::arch::asm!("wfi") };
+    }
+}
+
+#[allow(unused)]
+/// panics on purpose, run it last: with `PanicAction::Shutdown` the emulator
+/// exits non-zero, which `make panic-test` checks
+pub fn panic_action_test() {
+    panic!("panic_action_test panics on purpose");
 }
@@ -20,3 +20,27 @@ pub fn get_app_data(app_id: usize) -> &'static [u8] {
         )
     }
 }
+
+/// Name of app `app_id`, its elf file name without the extension.
+pub fn get_app_name(app_id: usize) -> &'static str {
+    extern "C" {
+        fn _app_names();
+    }
+    assert!(app_id < get_num_app());
+    // the names are packed one after another, each ended by a NUL
+    let mut start = _app_names as usize as *const u8;
+    unsafe {
+        for _ in 0..app_id {
+            while start.read_volatile() != 0 {
+                start = start.add(1);
+            }
+            start = start.add(1);
+        }
+        let mut end = start;
+        while end.read_volatile() != 0 {
+            end = end.add(1);
+        }
+        let name = core::slice::from_raw_parts(start, end as usize - start as usize);
+        core::str::from_utf8(name).unwrap()
+    }
+}
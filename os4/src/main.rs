@@ -13,6 +13,8 @@ extern crate alloc;
 #[macro_use]
 mod console;
 mod config;
+mod fdt;
+mod fs;
 mod lang_items;
 mod loader;
 mod logging;
@@ -39,13 +41,19 @@ fn clear_bss() {
 }
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(_hartid: usize, dtb: usize) -> ! {
     clear_bss();
     logging::init();
     println!("[kernel] Hello, world!");
-    mm::init();
+    mm::init(fdt::ram_end(dtb));
     println!("[kernel] back to world!");
+    #[cfg(feature = "mm_self_test")]
+    mm::self_test();
+    #[cfg(feature = "frame_fuzz")]
+    mm::frame_allocator_fuzz();
     mm::remap_test();
+    #[cfg(feature = "panic_test")]
+    lang_items::panic_action_test();
     trap::init();
     //trap::enable_interrupt();
     trap::enable_timer_interrupt();
@@ -1,5 +1,6 @@
 //! Implementation of physical and virtual address and page number.
 
+use super::page_table::{PAGE_TABLE_LEVELS, VPN_INDEX_BITS};
 use super::PageTableEntry;
 use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
 use core::fmt::{self, Debug, Formatter};
@@ -140,12 +141,12 @@ impl From<PhysPageNum> for PhysAddr {
 }
 
 impl VirtPageNum {
-    pub fn indexes(&self) -> [usize; 3] {
+    pub fn indexes(&self) -> [usize; PAGE_TABLE_LEVELS] {
         let mut vpn = self.0;
-        let mut idx = [0usize; 3];
-        for i in (0..3).rev() {
-            idx[i] = vpn & 511;
-            vpn >>= 9;
+        let mut idx = [0usize; PAGE_TABLE_LEVELS];
+        for i in (0..PAGE_TABLE_LEVELS).rev() {
+            idx[i] = vpn & ((1 << VPN_INDEX_BITS) - 1);
+            vpn >>= VPN_INDEX_BITS;
         }
         idx
     }
@@ -2,12 +2,15 @@
 //! controls all the frames in the operating system.
 
 use super::{PhysAddr, PhysPageNum};
-use crate::config::MEMORY_END;
+use crate::config::{DETERMINISTIC_FRAMES, HUGE_PAGE_SIZE, MEMORY_END, PAGE_SIZE};
 use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 
+const HUGE_PAGE_FRAMES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
 /// manage a frame which has the same lifecycle as the tracker
 pub struct FrameTracker {
     pub ppn: PhysPageNum,
@@ -16,12 +19,20 @@ pub struct FrameTracker {
 impl FrameTracker {
     pub fn new(ppn: PhysPageNum) -> Self {
         // page cleaning
+        Self::new_filled(ppn, 0)
+    }
+    /// Track `ppn` with every byte of the frame set to `fill`.
+    pub fn new_filled(ppn: PhysPageNum, fill: u8) -> Self {
         let bytes_array = ppn.get_bytes_array();
         for i in bytes_array {
-            *i = 0;
+            *i = fill;
         }
         Self { ppn }
     }
+    /// Track `ppn` as it is, with whatever its last owner left in it.
+    pub fn new_uninit(ppn: PhysPageNum) -> Self {
+        Self { ppn }
+    }
 }
 
 impl Debug for FrameTracker {
@@ -40,31 +51,62 @@ trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    fn free_count(&self) -> usize;
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum>;
 }
 
 /// an implementation for frame allocator
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
     recycled: Vec<usize>,
+    /// prefer `current` over `recycled`, see `DETERMINISTIC_FRAMES`
+    deterministic: bool,
 }
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
         self.current = l.0;
         self.end = r.0;
     }
+    /// number of frames the allocator was given, free or not
+    pub fn total_count(&self) -> usize {
+        self.end - self.start
+    }
+    /// whether `ppn` lies in the region handed to `init`
+    pub fn owns(&self, ppn: PhysPageNum) -> bool {
+        self.start <= ppn.0 && ppn.0 < self.end
+    }
+    /// Give the recycled frames right below `current` back to the part
+    /// never handed out, so `alloc_contiguous` can use them again. Returns
+    /// how many frames were coalesced.
+    pub fn compact(&mut self) -> usize {
+        self.recycled.sort_unstable();
+        let before = self.current;
+        while self.current > self.start && self.recycled.last() == Some(&(self.current - 1)) {
+            self.recycled.pop();
+            self.current -= 1;
+        }
+        before - self.current
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            deterministic: DETERMINISTIC_FRAMES,
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
+        if self.deterministic && self.current < self.end {
+            self.current += 1;
+            Some((self.current - 1).into())
+        } else if let Some(ppn) = self.recycled.pop() {
             Some(ppn.into())
         } else if self.current == self.end {
             None
@@ -82,6 +124,20 @@ impl FrameAllocator for StackFrameAllocator {
         // recycle
         self.recycled.push(ppn);
     }
+    fn free_count(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
+    /// Take `count` frames starting at a multiple of `align` from the part
+    /// never handed out. Frames skipped for alignment go to `recycled`.
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum> {
+        let start = (self.current + align - 1) / align * align;
+        if start + count > self.end {
+            return None;
+        }
+        self.recycled.extend(self.current..start);
+        self.current = start + count;
+        Some(start.into())
+    }
 }
 
 type FrameAllocatorImpl = StackFrameAllocator;
@@ -92,17 +148,62 @@ lazy_static! {
         unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
 }
 
-/// initiate the frame allocator using `ekernel` and `MEMORY_END`
-pub fn init_frame_allocator() {
+/// end of the physical memory in use, `MEMORY_END` unless less RAM was found
+static RAM_END: AtomicUsize = AtomicUsize::new(MEMORY_END);
+
+/// End of the physical memory the frame allocator and kernel space cover.
+pub fn memory_end() -> usize {
+    RAM_END.load(Ordering::Relaxed)
+}
+
+/// `memory_end`, clamped to `ram_end` if the RAM found is smaller. Panics if
+/// no memory would be left past the kernel image at `kernel_end`.
+fn checked_memory_end(kernel_end: usize, memory_end: usize, ram_end: Option<usize>) -> usize {
+    let end = match ram_end {
+        Some(ram_end) if ram_end < memory_end => {
+            warn!(
+                "[kernel] MEMORY_END {:#x} is past the end of RAM {:#x}, clamping",
+                memory_end, ram_end
+            );
+            ram_end
+        }
+        _ => memory_end,
+    };
+    assert!(
+        kernel_end < end,
+        "memory end {:#x} is not past the kernel image ending at {:#x}",
+        end,
+        kernel_end
+    );
+    end
+}
+
+/// initiate the frame allocator from `ekernel` to `MEMORY_END`, or to
+/// `ram_end` if that is lower
+pub fn init_frame_allocator(ram_end: Option<usize>) {
     extern "C" {
         fn ekernel();
     }
+    let end = checked_memory_end(ekernel as usize, MEMORY_END, ram_end);
+    RAM_END.store(end, Ordering::Relaxed);
     FRAME_ALLOCATOR.exclusive_access().init(
         PhysAddr::from(ekernel as usize).ceil(),
-        PhysAddr::from(MEMORY_END).floor(),
+        PhysAddr::from(end).floor(),
     );
 }
 
+/// free and total frame counts, read together
+pub fn frame_stats() -> (usize, usize) {
+    let allocator = FRAME_ALLOCATOR.exclusive_access();
+    (allocator.free_count(), allocator.total_count())
+}
+
+/// whether `ppn` is an allocatable frame, as opposed to the kernel image,
+/// MMIO or something outside RAM
+pub fn frame_in_range(ppn: PhysPageNum) -> bool {
+    FRAME_ALLOCATOR.exclusive_access().owns(ppn)
+}
+
 /// allocate a frame
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
@@ -111,11 +212,81 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(FrameTracker::new)
 }
 
+/// allocate a frame filled with `fill` instead of zero
+pub fn frame_alloc_filled(fill: u8) -> Option<FrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc()
+        .map(|ppn| FrameTracker::new_filled(ppn, fill))
+}
+
+/// allocate a frame without clearing it. It may still hold another task's
+/// or the kernel's data, so it must never reach a task that reads before
+/// writing; only for `MMAP_UNINITIALIZED`
+pub fn frame_alloc_uninit() -> Option<FrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc()
+        .map(FrameTracker::new_uninit)
+}
+
 /// deallocate a frame
 fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+/// manage a 2MB-aligned run of 512 frames backing one megapage
+pub struct HugeFrameTracker {
+    pub ppn: PhysPageNum,
+}
+
+impl HugeFrameTracker {
+    pub fn new(ppn: PhysPageNum) -> Self {
+        for i in 0..HUGE_PAGE_FRAMES {
+            for byte in PhysPageNum(ppn.0 + i).get_bytes_array() {
+                *byte = 0;
+            }
+        }
+        Self { ppn }
+    }
+}
+
+impl Drop for HugeFrameTracker {
+    fn drop(&mut self) {
+        for i in 0..HUGE_PAGE_FRAMES {
+            frame_dealloc(PhysPageNum(self.ppn.0 + i));
+        }
+    }
+}
+
+/// allocate the frames of a megapage
+pub fn huge_frame_alloc() -> Option<HugeFrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(HUGE_PAGE_FRAMES, HUGE_PAGE_FRAMES)
+        .map(HugeFrameTracker::new)
+}
+
+/// Like `huge_frame_alloc`, but on failure compact the allocator and try
+/// once more before giving up. A single frame never needs this: `frame_alloc`
+/// only fails with no frame free at all, which compacting cannot change.
+/// With an eviction scanner, this is where it would run before the retry.
+pub fn huge_frame_alloc_compacting() -> Option<HugeFrameTracker> {
+    huge_frame_alloc().or_else(|| {
+        let coalesced = FRAME_ALLOCATOR.exclusive_access().compact();
+        debug!(
+            "[kernel] megapage alloc failed, compacted {} frames",
+            coalesced
+        );
+        huge_frame_alloc()
+    })
+}
+
+/// number of frames that can still be allocated
+pub fn free_frame_count() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().free_count()
+}
+
 #[allow(unused)]
 /// a simple test for frame allocator
 pub fn frame_allocator_test() {
@@ -134,3 +305,177 @@ pub fn frame_allocator_test() {
     drop(v);
     info!("frame_allocator_test passed!");
 }
+
+#[allow(unused)]
+/// with deterministic allocation, the free order does not change later frames
+pub fn deterministic_frames_test() {
+    fn run(free_reversed: bool) -> Vec<usize> {
+        let mut allocator = StackFrameAllocator::new();
+        allocator.deterministic = true;
+        allocator.init(PhysPageNum(0x80000), PhysPageNum(0x80010));
+        let mut frames: Vec<PhysPageNum> = (0..4).map(|_| allocator.alloc().unwrap()).collect();
+        if free_reversed {
+            frames.reverse();
+        }
+        for ppn in frames {
+            allocator.dealloc(ppn);
+        }
+        (0..12).map(|_| allocator.alloc().unwrap().0).collect()
+    }
+    let first = run(false);
+    assert!(first == run(true));
+    assert!(first == (0x80004..0x80010).collect::<Vec<_>>());
+    info!("deterministic_frames_test passed!");
+}
+
+#[allow(unused)]
+/// allocated frames are in range, the kernel image and addresses past
+/// `MEMORY_END` are not
+pub fn frame_in_range_test() {
+    extern "C" {
+        fn skernel();
+    }
+    let frame = frame_alloc().unwrap();
+    assert!(frame_in_range(frame.ppn));
+    assert!(!frame_in_range(PhysAddr::from(skernel as usize).floor()));
+    assert!(!frame_in_range(PhysAddr::from(memory_end()).floor()));
+    assert!(!frame_in_range(PhysPageNum(usize::MAX >> 12)));
+    info!("frame_in_range_test passed!");
+}
+
+#[allow(unused)]
+/// a `MEMORY_END` past the RAM found is clamped to it, a smaller one is kept
+pub fn memory_end_clamp_test() {
+    let kernel_end = 0x8040_0000;
+    assert_eq!(checked_memory_end(kernel_end, MEMORY_END, None), MEMORY_END);
+    assert_eq!(
+        checked_memory_end(kernel_end, MEMORY_END, Some(MEMORY_END + HUGE_PAGE_SIZE)),
+        MEMORY_END
+    );
+    assert_eq!(
+        checked_memory_end(kernel_end, MEMORY_END, Some(0x8400_0000)),
+        0x8400_0000
+    );
+    info!("memory_end_clamp_test passed!");
+}
+
+#[allow(unused)]
+/// a contiguous request that fails because freed frames sit in `recycled`
+/// succeeds once `compact` has coalesced them; only bookkeeping is exercised
+pub fn compact_test() {
+    let mut allocator = StackFrameAllocator::new();
+    let start = HUGE_PAGE_FRAMES * 0x800;
+    allocator.init(
+        PhysPageNum(start),
+        PhysPageNum(start + 2 * HUGE_PAGE_FRAMES),
+    );
+    let frames: Vec<PhysPageNum> = (0..2 * HUGE_PAGE_FRAMES)
+        .map(|_| allocator.alloc().unwrap())
+        .collect();
+    // free the upper megapage's worth, in no particular order
+    for ppn in frames[HUGE_PAGE_FRAMES..].iter().rev().step_by(2) {
+        allocator.dealloc(*ppn);
+    }
+    for ppn in frames[HUGE_PAGE_FRAMES..].iter().step_by(2) {
+        allocator.dealloc(*ppn);
+    }
+    assert_eq!(allocator.free_count(), HUGE_PAGE_FRAMES);
+    assert!(allocator
+        .alloc_contiguous(HUGE_PAGE_FRAMES, HUGE_PAGE_FRAMES)
+        .is_none());
+    assert_eq!(allocator.compact(), HUGE_PAGE_FRAMES);
+    assert_eq!(allocator.free_count(), HUGE_PAGE_FRAMES);
+    assert_eq!(
+        allocator.alloc_contiguous(HUGE_PAGE_FRAMES, HUGE_PAGE_FRAMES),
+        Some(PhysPageNum(start + HUGE_PAGE_FRAMES))
+    );
+    // nothing left to coalesce
+    assert_eq!(allocator.compact(), 0);
+    info!("compact_test passed!");
+}
+
+#[allow(unused)]
+/// panics on purpose, run it last: a `MEMORY_END` below `ekernel` stops the
+/// boot with a message naming both, instead of an allocator handing out the
+/// kernel image
+pub fn memory_end_below_kernel_test() {
+    extern "C" {
+        fn ekernel();
+    }
+    let memory_end = ekernel as usize - PAGE_SIZE;
+    checked_memory_end(ekernel as usize, memory_end, None);
+    unreachable!("memory_end_below_kernel_test expects a panic above");
+}
+
+/// Random alloc, dealloc and contiguous alloc calls on a private
+/// `StackFrameAllocator` over the first frames of the real one. Only its
+/// bookkeeping is exercised, no frame is touched. Build with `frame_fuzz`.
+#[cfg(feature = "frame_fuzz")]
+pub fn frame_allocator_fuzz() {
+    use crate::timer::get_time_us;
+    use alloc::collections::BTreeSet;
+    const OPS: usize = 1_000_000;
+    // small enough to run out often, and to keep the linear double free
+    // check in `dealloc` from dominating
+    const FRAMES: usize = 1024;
+    const MAX_HELD: usize = FRAMES / 2;
+    let (l, r) = {
+        let global = FRAME_ALLOCATOR.exclusive_access();
+        (global.start, global.end.min(global.start + FRAMES))
+    };
+    let mut allocator = StackFrameAllocator::new();
+    allocator.init(l.into(), r.into());
+    let total = allocator.free_count();
+    // xorshift64, seeded so a failure can be replayed
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    // held frames, as a list to pick from and a set to catch a frame handed out twice
+    let mut held: Vec<usize> = Vec::new();
+    let mut held_set = BTreeSet::new();
+    let take = |ppn: usize, held: &mut Vec<usize>, held_set: &mut BTreeSet<usize>| {
+        assert!(l <= ppn && ppn < r, "frame {:#x} out of range", ppn);
+        assert!(held_set.insert(ppn), "frame {:#x} handed out twice", ppn);
+        held.push(ppn);
+    };
+    let start_us = get_time_us();
+    for _ in 0..OPS {
+        match next() % 8 {
+            0..=3 if held.len() < MAX_HELD => {
+                if let Some(ppn) = allocator.alloc() {
+                    take(ppn.0, &mut held, &mut held_set);
+                }
+            }
+            4 if held.len() < MAX_HELD => {
+                let count = 1 + next() % 8;
+                let align = 1 << (next() % 4);
+                if let Some(ppn) = allocator.alloc_contiguous(count, align) {
+                    assert_eq!(ppn.0 % align, 0);
+                    (ppn.0..ppn.0 + count).for_each(|ppn| take(ppn, &mut held, &mut held_set));
+                }
+            }
+            _ if !held.is_empty() => {
+                let ppn = held.swap_remove(next() % held.len());
+                assert!(held_set.remove(&ppn), "frame {:#x} freed twice", ppn);
+                allocator.dealloc(ppn.into());
+            }
+            _ => {}
+        }
+        assert_eq!(allocator.free_count() + held.len(), total);
+    }
+    let elapsed_us = (get_time_us() - start_us).max(1);
+    for ppn in held.drain(..) {
+        allocator.dealloc(ppn.into());
+    }
+    held_set.clear();
+    assert_eq!(allocator.free_count(), total);
+    info!(
+        "frame_allocator_fuzz passed! {} ops, {} ops/s",
+        OPS,
+        OPS as u64 * 1_000_000 / elapsed_us as u64
+    );
+}
@@ -2,10 +2,39 @@
 
 use crate::config::KERNEL_HEAP_SIZE;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ops::Range;
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// `LockedHeap` that also remembers the most bytes it has had allocated
+struct TrackedHeap {
+    heap: LockedHeap,
+    /// only updated with `heap` locked
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for TrackedHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.heap.lock();
+        let ptr = heap.alloc(layout).map_or(null_mut(), |ptr| ptr.as_ptr());
+        self.peak
+            .fetch_max(heap.stats_alloc_actual(), Ordering::Relaxed);
+        ptr
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap
+            .lock()
+            .dealloc(NonNull::new_unchecked(ptr), layout);
+    }
+}
 
 #[global_allocator]
 /// heap allocator instance
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: TrackedHeap = TrackedHeap {
+    heap: LockedHeap::empty(),
+    peak: AtomicUsize::new(0),
+};
 
 #[alloc_error_handler]
 /// panic when heap allocation error occurs
@@ -16,15 +45,50 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 /// heap space ([u8; KERNEL_HEAP_SIZE])
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
+/// Check that a heap of `size` bytes at `start` is not empty and ends
+/// before `frames_start`, where the frame allocator's memory begins. The
+/// allocator would otherwise only fail at the first allocation, or hand out
+/// frames still in use as heap.
+fn check_heap_region(start: usize, size: usize, frames_start: usize) {
+    assert!(size > 0, "KERNEL_HEAP_SIZE is 0, the kernel needs a heap");
+    assert!(
+        start
+            .checked_add(size)
+            .map_or(false, |end| end <= frames_start),
+        "heap [{:#x}, {:#x}) overlaps the frames from {:#x}",
+        start,
+        start.wrapping_add(size),
+        frames_start
+    );
+}
+
 /// initiate heap allocator
 pub fn init_heap() {
+    extern "C" {
+        fn ekernel();
+    }
     unsafe {
-        HEAP_ALLOCATOR
-            .lock()
-            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+        let start = HEAP_SPACE.as_ptr() as usize;
+        check_heap_region(start, KERNEL_HEAP_SIZE, ekernel as usize);
+        HEAP_ALLOCATOR.heap.lock().init(start, KERNEL_HEAP_SIZE);
     }
 }
 
+/// where the kernel heap lies, inside the kernel image's .bss
+pub fn heap_range() -> Range<usize> {
+    let start = unsafe { HEAP_SPACE.as_ptr() as usize };
+    start..start + KERNEL_HEAP_SIZE
+}
+
+/// bytes of kernel heap in use and the most ever in use, read together
+pub fn heap_stats() -> (usize, usize) {
+    let heap = HEAP_ALLOCATOR.heap.lock();
+    (
+        heap.stats_alloc_actual(),
+        HEAP_ALLOCATOR.peak.load(Ordering::Relaxed),
+    )
+}
+
 #[allow(unused)]
 pub fn heap_test() {
     use alloc::boxed::Box;
@@ -49,3 +113,16 @@ pub fn heap_test() {
     drop(v);
     info!("heap_test passed!");
 }
+
+#[allow(unused)]
+/// panics on purpose, run it last: a zero `KERNEL_HEAP_SIZE` stops the boot
+/// with a message naming it, instead of the first allocation failing
+pub fn heap_size_zero_test() {
+    extern "C" {
+        fn ekernel();
+    }
+    let start = unsafe { HEAP_SPACE.as_ptr() as usize };
+    check_heap_region(start, KERNEL_HEAP_SIZE, ekernel as usize);
+    check_heap_region(start, 0, ekernel as usize);
+    unreachable!("heap_size_zero_test expects a panic above");
+}
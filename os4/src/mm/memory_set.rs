@@ -1,13 +1,18 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
-use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::{flush_tlb_range, satp_mode, PTEFlags, PageTable, PageTableEntry, SATP_MODE_SV39};
+use super::{
+    frame_alloc, frame_alloc_filled, frame_alloc_uninit, free_frame_count,
+    huge_frame_alloc_compacting, memory_end, FrameTracker, HugeFrameTracker,
+};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use alloc::collections::BTreeMap;
+use crate::config::{HUGE_PAGE_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::syscall::errno::{EFAULT, EINVAL};
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ops::Range;
 use lazy_static::*;
 use riscv::register::satp;
 use spin::Mutex;
@@ -34,7 +39,10 @@ lazy_static! {
 /// memory set structure, controls virtual-memory space
 pub struct MemorySet {
     page_table: PageTable,
+    /// sorted by start vpn and never overlapping, see `check_invariants`
     areas: Vec<MapArea>,
+    /// pages pinned by `sys_mlock`, skipped by `coldest_page`
+    locked: BTreeSet<VirtPageNum>,
 }
 
 impl MemorySet {
@@ -42,10 +50,18 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            locked: BTreeSet::new(),
         }
     }
     pub fn token(&self) -> usize {
-        self.page_table.token()
+        let token = self.page_table.token();
+        debug_assert_eq!(
+            satp_mode(token),
+            SATP_MODE_SV39,
+            "token {:#x} is not Sv39",
+            token
+        );
+        token
     }
     /// Assume that no conflicts.
     pub fn insert_framed_area(
@@ -59,12 +75,121 @@ impl MemorySet {
             None,
         );
     }
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
+    /// Map `[start_va, end_va)` eagerly with frames filled with `fill`.
+    pub fn insert_filled_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        fill: u8,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.map_filled(&mut self.page_table, fill);
+        self.insert_sorted(map_area);
+    }
+    /// Map `[start_va, end_va)` eagerly with frames that are not cleared,
+    /// see `frame_alloc_uninit` for why that is dangerous.
+    pub fn insert_uninit_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.map_uninit(&mut self.page_table);
+        self.insert_sorted(map_area);
+    }
+    /// Reserve `[start_va, end_va)` without frames; pages are faulted in on
+    /// first access.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        self.insert_sorted(MapArea::new(start_va, end_va, MapType::Framed, permission));
+    }
+    /// Map `[start_va, end_va)` with 2MB megapages; both ends must be aligned
+    /// to `HUGE_PAGE_SIZE`. Maps nothing and returns false if no aligned run
+    /// of frames is left, even after compacting the frame allocator.
+    pub fn insert_huge_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        assert!(start_va.0 % HUGE_PAGE_SIZE == 0 && end_va.0 % HUGE_PAGE_SIZE == 0);
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Huge, permission);
+        for va in (start_va.0..end_va.0).step_by(HUGE_PAGE_SIZE) {
+            match huge_frame_alloc_compacting() {
+                Some(frame) => {
+                    map_area
+                        .huge_frames
+                        .insert(VirtAddr::from(va).floor(), frame);
+                }
+                None => return false,
+            }
+        }
+        self.push(map_area, None);
+        true
+    }
+    /// Whether unmapping `[start_vpn, end_vpn)` would cut a megapage in two.
+    pub fn splits_huge_page(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let pages = HUGE_PAGE_SIZE / PAGE_SIZE;
+        self.areas
+            .iter()
+            .filter(|area| area.map_type == MapType::Huge)
+            .any(|area| {
+                (area.contains(start_vpn) && start_vpn.0 % pages != 0)
+                    || (area.contains(end_vpn) && end_vpn.0 % pages != 0)
+            })
+    }
+    /// Whether `[start_vpn, end_vpn)` overlaps a megapage area.
+    pub fn overlaps_huge(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| {
+            area.map_type == MapType::Huge
+                && area.vpn_range.get_start() < end_vpn
+                && start_vpn < area.vpn_range.get_end()
+        })
+    }
+    /// Map `map_area` and fill it with `data`, given as the offset of its
+    /// first byte into the first page and the bytes.
+    fn push(&mut self, mut map_area: MapArea, data: Option<(usize, &[u8])>) {
         map_area.map(&mut self.page_table);
-        if let Some(data) = data {
-            map_area.copy_data(&mut self.page_table, data);
+        if let Some((offset, data)) = data {
+            map_area.copy_data(&self.page_table, offset, data);
+        }
+        self.insert_sorted(map_area);
+    }
+    /// Add `map_area` to `areas` before the first area starting after it.
+    fn insert_sorted(&mut self, map_area: MapArea) {
+        let start = map_area.vpn_range.get_start();
+        let idx = self
+            .areas
+            .partition_point(|area| area.vpn_range.get_start() <= start);
+        self.areas.insert(idx, map_area);
+    }
+    /// Panic if `areas` is out of order or overlapping, or if a frame an
+    /// area holds is not what the page table maps there.
+    pub fn check_invariants(&self) {
+        for pair in self.areas.windows(2) {
+            let (l, r) = (&pair[0].vpn_range, &pair[1].vpn_range);
+            assert!(
+                l.get_end() <= r.get_start(),
+                "areas out of order or overlapping: [{:#x}, {:#x}) then [{:#x}, {:#x})",
+                l.get_start().0,
+                l.get_end().0,
+                r.get_start().0,
+                r.get_end().0
+            );
+        }
+        for area in self.areas.iter() {
+            for (vpn, frame) in area.data_frames.iter() {
+                assert!(area.contains(*vpn));
+                let pte = self.translate(*vpn).unwrap();
+                assert!(pte.is_valid() && pte.ppn() == frame.ppn);
+            }
         }
-        self.areas.push(map_area);
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
@@ -131,7 +256,7 @@ impl MemorySet {
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                memory_end().into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
@@ -140,21 +265,27 @@ impl MemorySet {
         memory_set
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// also returns user_sp, entry point and the pages the segments span.
+    ///
+    /// Returns the reason instead if `elf_data` is not a RISC-V 64 executable.
+    pub fn from_elf(elf_data: &[u8]) -> Result<(Self, usize, usize, Range<usize>), &'static str> {
+        validate_elf_header(elf_data)?;
+        let elf = xmas_elf::ElfFile::new(elf_data)?;
+        let elf_header = elf.header;
+        let ph_count = elf_header.pt2.ph_count();
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
+        let mut min_start_vpn: Option<VirtPageNum> = None;
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i)?;
+            if ph.get_type()? == xmas_elf::program::Type::Load {
+                let file_range = ph.offset() as usize..(ph.offset() + ph.file_size()) as usize;
+                if file_range.end > elf.input.len() {
+                    return Err("segment exceeds file size");
+                }
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
                 let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
                 let mut map_perm = MapPermission::U;
@@ -170,9 +301,12 @@ impl MemorySet {
                 }
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
                 max_end_vpn = map_area.vpn_range.get_end();
+                let start_vpn = map_area.vpn_range.get_start();
+                min_start_vpn = Some(min_start_vpn.map_or(start_vpn, |vpn| vpn.min(start_vpn)));
+                // a segment may start mid-page, its bytes have to land there
                 memory_set.push(
                     map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
+                    Some((start_va.page_offset(), &elf.input[file_range])),
                 );
             }
         }
@@ -201,11 +335,13 @@ impl MemorySet {
             ),
             None,
         );
-        (
+        let image_start: VirtAddr = min_start_vpn.unwrap_or(max_end_vpn).into();
+        Ok((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+            image_start.0..max_end_va.0,
+        ))
     }
     pub fn activate(&self) {
         let satp = self.page_table.token();
@@ -217,17 +353,381 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    /// Unmap `[start_vpn, end_vpn)` and release its frames. Areas covering
+    /// the range are trimmed, and an area unmapped in the middle is split in two.
     pub fn unmap(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        self.set_locked(start_vpn, end_vpn, false);
+        let mut areas = Vec::new();
+        for mut area in self.areas.drain(..) {
+            let l = area.vpn_range.get_start();
+            let r = area.vpn_range.get_end();
+            if r <= start_vpn || end_vpn <= l {
+                areas.push(area);
+                continue;
+            }
+            for vpn in VPNRange::new(l.max(start_vpn), r.min(end_vpn)) {
+                area.unmap_one(&mut self.page_table, vpn);
+            }
+            let tail = if end_vpn < r {
+                Some(area.split_off(end_vpn))
+            } else {
+                None
+            };
+            if l < start_vpn {
+                area.vpn_range = VPNRange::new(l, start_vpn);
+                areas.push(area);
+            }
+            if let Some(tail) = tail {
+                areas.push(tail);
+            }
+        }
+        self.areas = areas;
+        flush_tlb_range(start_vpn, end_vpn);
+    }
+    /// Give `[start_vpn, end_vpn)` the permission `map_perm`, splitting the
+    /// areas at the ends of the range. Resident pages are re-flagged now,
+    /// pages faulted in later are mapped with the new permission.
+    pub fn protect(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+        map_perm: MapPermission,
+    ) {
+        let mut areas = Vec::new();
+        for mut area in self.areas.drain(..) {
+            let l = area.vpn_range.get_start();
+            let r = area.vpn_range.get_end();
+            if r <= start_vpn || end_vpn <= l {
+                areas.push(area);
+                continue;
+            }
+            let tail = if end_vpn < r {
+                Some(area.split_off(end_vpn))
+            } else {
+                None
+            };
+            let mut middle = if l < start_vpn {
+                let middle = area.split_off(start_vpn);
+                areas.push(area);
+                middle
+            } else {
+                area
+            };
+            middle.set_perm(&mut self.page_table, map_perm);
+            areas.push(middle);
+            if let Some(tail) = tail {
+                areas.push(tail);
+            }
+        }
+        self.areas = areas;
+        flush_tlb_range(start_vpn, end_vpn);
+    }
+    /// Pin (`lock`) or unpin the pages of `[start_vpn, end_vpn)`, which the
+    /// caller has checked lie in areas.
+    pub fn set_locked(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum, lock: bool) {
         for vpn in VPNRange::new(start_vpn, end_vpn) {
-            self.page_table.unmap(vpn);
+            if lock {
+                self.locked.insert(vpn);
+            } else {
+                self.locked.remove(&vpn);
+            }
         }
     }
+    /// The resident user page best given up first: one not accessed since
+    /// its `A` bit was last clear if there is one, never a locked page.
+    pub fn coldest_page(&self) -> Option<VirtPageNum> {
+        let candidates = self
+            .areas
+            .iter()
+            .filter(|area| {
+                area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U)
+            })
+            .flat_map(|area| area.data_frames.keys())
+            .filter(|vpn| !self.locked.contains(vpn));
+        let mut fallback = None;
+        for vpn in candidates {
+            let accessed = self
+                .translate(*vpn)
+                .map_or(false, |pte| pte.flags().contains(PTEFlags::A));
+            if !accessed {
+                return Some(*vpn);
+            }
+            fallback.get_or_insert(*vpn);
+        }
+        fallback
+    }
+    /// Whether `vpn` is taken, either by an area (resident or not) or by a
+    /// valid mapping outside any area such as the trampoline.
+    pub fn is_reserved(&self, vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| area.contains(vpn))
+            || self.translate(vpn).map_or(false, |pte| pte.is_valid())
+    }
+    /// Whether `vpn` lies inside one of the areas.
+    pub fn in_area(&self, vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| area.contains(vpn))
+    }
+    /// One byte per page of `[start_vpn, end_vpn)`: 1 if backed by a frame,
+    /// 0 if only reserved. `None` if some page is outside every area.
+    pub fn residency(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> Option<Vec<u8>> {
+        VPNRange::new(start_vpn, end_vpn)
+            .into_iter()
+            .map(|vpn| {
+                if !self.in_area(vpn) {
+                    return None;
+                }
+                Some(self.translate(vpn).map_or(0, |pte| pte.is_valid() as u8))
+            })
+            .collect()
+    }
+    /// Back a non-resident page of a framed user area with a fresh zero frame.
+    /// Returns false if `vpn` is outside every such area, already resident,
+    /// not permitted for `access`, or no frame is left.
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        let page_table = &mut self.page_table;
+        match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area)
+                if area.map_type == MapType::Framed
+                    && area.map_perm.contains(access | MapPermission::U)
+                    && !area.data_frames.contains_key(&vpn)
+                    && free_frame_count() > 0 =>
+            {
+                area.map_one(page_table, vpn);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Release the resident frames in `[start_vpn, end_vpn)` but keep the
+    /// areas, so the next access faults in a zero page. Returns the number
+    /// of frames released. Megapages cannot fault back in and are kept.
+    pub fn discard(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> usize {
+        let mut released = 0;
+        for area in self.areas.iter_mut() {
+            let resident: Vec<VirtPageNum> = area
+                .data_frames
+                .range(start_vpn..end_vpn)
+                .map(|(vpn, _)| *vpn)
+                .collect();
+            for vpn in resident {
+                area.unmap_one(&mut self.page_table, vpn);
+                released += 1;
+            }
+        }
+        flush_tlb_range(start_vpn, end_vpn);
+        released
+    }
+    /// Every resident `(vpn, ppn, flags)` across all areas, in area order.
+    /// Reserved but non-resident pages are skipped.
+    pub fn iter_mappings(&self) -> impl Iterator<Item = (VirtPageNum, PhysPageNum, PTEFlags)> + '_ {
+        self.areas
+            .iter()
+            .flat_map(|area| area.vpn_range.into_iter())
+            .filter_map(move |vpn| {
+                self.page_table
+                    .translate(vpn)
+                    .filter(|pte| pte.is_valid())
+                    .map(|pte| (vpn, pte.ppn(), pte.flags()))
+            })
+    }
+    /// Pages in the areas whose contents differ between `self` and `other`,
+    /// in vpn order. A page resident in only one of them differs; one that
+    /// is resident in neither, like an unfaulted lazy page, does not.
+    pub fn diff(&self, other: &MemorySet) -> Vec<VirtPageNum> {
+        let resident = |memory_set: &MemorySet| -> BTreeMap<VirtPageNum, PhysPageNum> {
+            memory_set
+                .iter_mappings()
+                .map(|(vpn, ppn, _)| (vpn, ppn))
+                .collect()
+        };
+        let (ours, theirs) = (resident(self), resident(other));
+        let vpns: BTreeSet<VirtPageNum> = ours.keys().chain(theirs.keys()).copied().collect();
+        vpns.into_iter()
+            .filter(|vpn| match (ours.get(vpn), theirs.get(vpn)) {
+                (Some(a), Some(b)) => a.get_bytes_array() != b.get_bytes_array(),
+                _ => true,
+            })
+            .collect()
+    }
+    /// Check that `[va, va + len)` lies in user pages allowing `access`,
+    /// faulting in reserved lazy pages on the way.
+    pub fn prepare_user_range(&mut self, va: usize, len: usize, access: MapPermission) -> bool {
+        let end = match va.checked_add(len) {
+            Some(end) if end <= TRAP_CONTEXT => end,
+            _ => return false,
+        };
+        if len == 0 {
+            return true;
+        }
+        let flags = PTEFlags::from_bits((access | MapPermission::U).bits).unwrap();
+        for vpn in VPNRange::new(VirtAddr::from(va).floor(), VirtAddr::from(end).ceil()) {
+            let resident = self.translate(vpn).map_or(false, |pte| pte.is_valid());
+            if !resident && !self.handle_page_fault(vpn, access) {
+                return false;
+            }
+            if !self.translate(vpn).unwrap().flags().contains(flags) {
+                return false;
+            }
+        }
+        true
+    }
+    /// Whether `[va, va + len)` lies in resident user pages allowing `access`,
+    /// without faulting anything in.
+    #[cfg(feature = "peek_task")]
+    fn user_range_resident(&self, va: usize, len: usize, access: MapPermission) -> bool {
+        let end = match va.checked_add(len) {
+            Some(end) if end <= TRAP_CONTEXT => end,
+            _ => return false,
+        };
+        let flags = PTEFlags::from_bits((access | MapPermission::U).bits).unwrap() | PTEFlags::V;
+        len == 0
+            || VPNRange::new(VirtAddr::from(va).floor(), VirtAddr::from(end).ceil())
+                .into_iter()
+                .all(|vpn| {
+                    self.translate(vpn)
+                        .map_or(false, |pte| pte.flags().contains(flags))
+                })
+    }
+    /// Run `f` on each `(user page slice, offset into the buffer)` of
+    /// `[va, va + len)`, which must have been prepared.
+    fn for_each_user_chunk(&self, va: usize, len: usize, mut f: impl FnMut(&mut [u8], usize)) {
+        let mut start = va;
+        let end = va + len;
+        while start < end {
+            let start_va = VirtAddr::from(start);
+            let mut vpn = start_va.floor();
+            let ppn = self.translate(vpn).unwrap().ppn();
+            vpn.step();
+            let end_va = VirtAddr::from(vpn).min(VirtAddr::from(end));
+            let chunk_end = if end_va.page_offset() == 0 {
+                PAGE_SIZE
+            } else {
+                end_va.page_offset()
+            };
+            f(
+                &mut ppn.get_bytes_array()[start_va.page_offset()..chunk_end],
+                start - va,
+            );
+            start = end_va.into();
+        }
+    }
+    /// Copy `src` to user address `dst_va`, returning the bytes copied or
+    /// `EFAULT` if any page is missing or not user-writable. Nothing is
+    /// written on failure.
+    pub fn copy_to_user(&mut self, dst_va: usize, src: &[u8]) -> isize {
+        if !self.prepare_user_range(dst_va, src.len(), MapPermission::W) {
+            return EFAULT;
+        }
+        self.for_each_user_chunk(dst_va, src.len(), |page, offset| {
+            page.copy_from_slice(&src[offset..offset + page.len()]);
+        });
+        src.len() as isize
+    }
+    /// Fill `dst` from user address `src_va`, returning the bytes copied or
+    /// `EFAULT` if any page is missing or not user-readable.
+    pub fn copy_from_user(&mut self, dst: &mut [u8], src_va: usize) -> isize {
+        if !self.prepare_user_range(src_va, dst.len(), MapPermission::R) {
+            return EFAULT;
+        }
+        self.for_each_user_chunk(src_va, dst.len(), |page, offset| {
+            dst[offset..offset + page.len()].copy_from_slice(page);
+        });
+        dst.len() as isize
+    }
+    /// Like `copy_from_user`, but for reading another task: every page has
+    /// to be resident already, nothing is faulted in on its behalf.
+    #[cfg(feature = "peek_task")]
+    pub fn peek(&self, dst: &mut [u8], src_va: usize) -> isize {
+        if !self.user_range_resident(src_va, dst.len(), MapPermission::R) {
+            return EFAULT;
+        }
+        self.for_each_user_chunk(src_va, dst.len(), |page, offset| {
+            dst[offset..offset + page.len()].copy_from_slice(page);
+        });
+        dst.len() as isize
+    }
+    /// Number of frames currently backing the areas of this memory set.
+    pub fn resident_pages(&self) -> usize {
+        self.areas.iter().map(|area| area.frame_count()).sum()
+    }
+    /// Tear down the user part of the space when its task exits: unmap every
+    /// area except the TrapContext page, releasing their frames and the page
+    /// tables left empty. The trampoline is not an area and stays mapped, and
+    /// the kernel stack lives in `KERNEL_SPACE`. Calling it again does nothing.
+    pub fn unmap_all_user(&mut self) {
+        let trap_cx_vpn = VirtAddr::from(TRAP_CONTEXT).floor();
+        let page_table = &mut self.page_table;
+        self.locked.clear();
+        self.areas.retain_mut(|area| {
+            if area.vpn_range.get_start() == trap_cx_vpn {
+                return true;
+            }
+            area.unmap(page_table);
+            false
+        });
+        self.page_table.free_empty_tables();
+    }
+    /// `(start, end, frames)` of every area that still holds frames, but
+    /// the TrapContext page, which is expected to stay with its task.
+    fn unreleased_areas(&self) -> Vec<(VirtPageNum, VirtPageNum, usize)> {
+        let trap_cx_vpn = VirtAddr::from(TRAP_CONTEXT).floor();
+        self.areas
+            .iter()
+            .filter(|area| area.vpn_range.get_start() != trap_cx_vpn)
+            .filter(|area| area.frame_count() > 0)
+            .map(|area| {
+                (
+                    area.vpn_range.get_start(),
+                    area.vpn_range.get_end(),
+                    area.frame_count(),
+                )
+            })
+            .collect()
+    }
+    /// Log every area of an exited task that still holds frames, see
+    /// `unreleased_areas`.
+    #[allow(unused)]
+    pub fn report_unreleased(&self, task_id: usize) {
+        for (start, end, frames) in self.unreleased_areas() {
+            warn!(
+                "[kernel] exited task {} still holds {} frames in [{:?}, {:?})",
+                task_id, frames, start, end
+            );
+        }
+    }
+}
+
+/// Check the ELF identification and header fields `from_elf` relies on,
+/// reading them directly from the raw bytes.
+fn validate_elf_header(elf_data: &[u8]) -> Result<(), &'static str> {
+    const ELF64_HEADER_SIZE: usize = 64;
+    const ELFCLASS64: u8 = 2;
+    const ELFDATA2LSB: u8 = 1;
+    const ET_EXEC: u16 = 2;
+    const EM_RISCV: u16 = 243;
+    if elf_data.len() < ELF64_HEADER_SIZE {
+        return Err("truncated elf header");
+    }
+    if elf_data[..4] != [0x7f, 0x45, 0x4c, 0x46] {
+        return Err("invalid elf magic");
+    }
+    if elf_data[4] != ELFCLASS64 || elf_data[5] != ELFDATA2LSB {
+        return Err("not a little-endian ELF64 file");
+    }
+    if u16::from_le_bytes([elf_data[16], elf_data[17]]) != ET_EXEC {
+        return Err("not an executable");
+    }
+    if u16::from_le_bytes([elf_data[18], elf_data[19]]) != EM_RISCV {
+        return Err("not a RISC-V executable");
+    }
+    Ok(())
 }
 
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// megapages of a `Huge` area, keyed by their first vpn
+    huge_frames: BTreeMap<VirtPageNum, HugeFrameTracker>,
     map_type: MapType,
     map_perm: MapPermission,
 }
@@ -244,6 +744,7 @@ impl MapArea {
         Self {
             vpn_range: VPNRange::new(start_vpn, end_vpn),
             data_frames: BTreeMap::new(),
+            huge_frames: BTreeMap::new(),
             map_type,
             map_perm,
         }
@@ -259,61 +760,133 @@ impl MapArea {
                 ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
             }
+            MapType::Huge => unreachable!("megapages are mapped by MapArea::map"),
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
     }
-    #[allow(unused)]
+    /// Change the permission of the area, re-mapping its resident pages.
+    pub fn set_perm(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        self.map_perm = map_perm;
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for (vpn, frame) in self.data_frames.iter() {
+            page_table.unmap(*vpn);
+            page_table.map(*vpn, frame.ppn, pte_flags);
+        }
+        for (vpn, frames) in self.huge_frames.iter() {
+            page_table.unmap_huge(*vpn);
+            page_table.map_huge(*vpn, frames.ppn, pte_flags);
+        }
+    }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         #[allow(clippy::single_match)]
         match self.map_type {
             MapType::Framed => {
-                self.data_frames.remove(&vpn);
+                // a lazily discarded page has no frame and no mapping
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
+            }
+            MapType::Huge => {
+                // only the first vpn of a megapage carries its mapping
+                if self.huge_frames.remove(&vpn).is_some() {
+                    page_table.unmap_huge(vpn);
+                }
+                return;
             }
             _ => {}
         }
         page_table.unmap(vpn);
     }
+    /// Number of 4KB frames held by this area.
+    pub fn frame_count(&self) -> usize {
+        self.data_frames.len() + self.huge_frames.len() * HUGE_PAGE_SIZE / PAGE_SIZE
+    }
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    /// Split the area at `vpn`, returning the part `[vpn, end)` along with its frames.
+    pub fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        let end = self.vpn_range.get_end();
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), vpn);
+        Self {
+            vpn_range: VPNRange::new(vpn, end),
+            data_frames: self.data_frames.split_off(&vpn),
+            huge_frames: self.huge_frames.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        }
+    }
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.map_type == MapType::Huge {
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            for (vpn, frame) in self.huge_frames.iter() {
+                page_table.map_huge(*vpn, frame.ppn, pte_flags);
+            }
+            return;
+        }
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
+    /// Map every page of a framed area with frames filled with `fill`.
+    pub fn map_filled(&mut self, page_table: &mut PageTable, fill: u8) {
+        assert_eq!(self.map_type, MapType::Framed);
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        for vpn in self.vpn_range {
+            let frame = frame_alloc_filled(fill).unwrap();
+            page_table.map(vpn, frame.ppn, pte_flags);
+            self.data_frames.insert(vpn, frame);
+        }
+    }
+    /// Like `map_filled`, leaving the frames as they were.
+    pub fn map_uninit(&mut self, page_table: &mut PageTable) {
+        assert_eq!(self.map_type, MapType::Framed);
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        for vpn in self.vpn_range {
+            let frame = frame_alloc_uninit().unwrap();
+            page_table.map(vpn, frame.ppn, pte_flags);
+            self.data_frames.insert(vpn, frame);
+        }
+    }
     #[allow(unused)]
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
             self.unmap_one(page_table, vpn);
         }
     }
-    /// data: start-aligned but maybe with shorter length
-    /// assume that all frames were cleared before
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
+    /// Copy `data` into the area from `offset` bytes into its first page on,
+    /// across as many frames as it takes. The rest of the pages written to,
+    /// before `offset` and past the end of `data`, is zeroed rather than
+    /// trusted to be clean.
+    pub fn copy_data(&mut self, page_table: &PageTable, offset: usize, data: &[u8]) {
         assert_eq!(self.map_type, MapType::Framed);
-        let mut start: usize = 0;
-        let mut current_vpn = self.vpn_range.get_start();
-        let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
-            let dst = &mut page_table
-                .translate(current_vpn)
-                .unwrap()
-                .ppn()
-                .get_bytes_array()[..src.len()];
-            dst.copy_from_slice(src);
-            start += PAGE_SIZE;
-            if start >= len {
-                break;
-            }
-            current_vpn.step();
+        assert!(offset < PAGE_SIZE);
+        let end = offset + data.len();
+        let area_len = (self.vpn_range.get_end().0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+        assert!(end <= area_len, "data overruns the area");
+        let mut vpn = self.vpn_range.get_start();
+        let mut page_start = 0;
+        while page_start < end {
+            let frame = page_table.translate(vpn).unwrap().ppn().get_bytes_array();
+            let lo = offset.max(page_start) - page_start;
+            let hi = end.min(page_start + PAGE_SIZE) - page_start;
+            frame[..lo].fill(0);
+            frame[lo..hi]
+                .copy_from_slice(&data[page_start + lo - offset..page_start + hi - offset]);
+            frame[hi..].fill(0);
+            page_start += PAGE_SIZE;
+            vpn.step();
         }
     }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+/// map type for memory set: identical, framed or backed by megapages
 pub enum MapType {
     Identical,
     Framed,
+    Huge,
 }
 
 bitflags! {
@@ -326,6 +899,28 @@ bitflags! {
     }
 }
 
+impl MapPermission {
+    /// User permission for the `port` of `sys_mmap` and `sys_mprotect`, bit 0
+    /// for R, 1 for W and 2 for X. `None` if no bit or an unknown bit is set.
+    pub fn from_port(port: usize) -> Option<Self> {
+        if (port & !0x7) != 0 || (port & 0x7) == 0 {
+            return None;
+        }
+        Some(Self::from_bits((port as u8) << 1).unwrap() | Self::U)
+    }
+}
+
+/// Bounds of `[start, start + len)` as given to `mmap`, `munmap` and
+/// `mprotect`. An unaligned `start`, a `len` of 0 or a range wrapping around
+/// the address space is `EINVAL`.
+pub fn user_range(start: usize, len: usize) -> Result<(VirtAddr, VirtAddr), isize> {
+    let start_va = VirtAddr::from(start);
+    match start.checked_add(len) {
+        Some(end) if len != 0 && start_va.aligned() => Ok((start_va, VirtAddr::from(end))),
+        _ => Err(EINVAL),
+    }
+}
+
 #[allow(unused)]
 pub fn remap_test() {
     let mut kernel_space = KERNEL_SPACE.lock();
@@ -349,3 +944,515 @@ pub fn remap_test() {
         .executable());
     info!("remap_test passed!");
 }
+
+/// Kernel address of the page `self_test` maps, clear of the identity map
+/// and the kernel stacks.
+const SELF_TEST_VA: usize = 0x1_0000_0000;
+
+/// Whether `va`, mapped in the active page table, reaches the frame `ppn`:
+/// a pattern written through one side reads back the same through the other.
+fn round_trip_agrees(va: VirtAddr, ppn: PhysPageNum) -> bool {
+    let virt = va.0 as *mut u64;
+    let phys = ppn.get_mut::<u64>() as *mut u64;
+    unsafe {
+        phys.write_volatile(0x0123_4567_89ab_cdef);
+        if virt.read_volatile() != 0x0123_4567_89ab_cdef {
+            return false;
+        }
+        virt.write_volatile(0xfedc_ba98_7654_3210);
+        phys.read_volatile() == 0xfedc_ba98_7654_3210
+    }
+}
+
+/// Map a scratch page in kernel space, check that it reaches its frame both
+/// ways, then unmap it and check the walk no longer finds it. A kernel
+/// access to it would fault with no handler to recover, so that is as close
+/// to watching the fault as it gets. Build with `mm_self_test`.
+#[cfg(feature = "mm_self_test")]
+pub fn self_test() {
+    let mut kernel_space = KERNEL_SPACE.lock();
+    let start = VirtAddr::from(SELF_TEST_VA);
+    let end = VirtAddr::from(SELF_TEST_VA + PAGE_SIZE);
+    kernel_space.insert_framed_area(start, end, MapPermission::R | MapPermission::W);
+    let ppn = kernel_space.translate(start.floor()).unwrap().ppn();
+    let agrees = round_trip_agrees(start, ppn);
+    kernel_space.unmap(start.floor(), end.floor());
+    let unmapped = kernel_space
+        .translate(start.floor())
+        .map_or(true, |pte| !pte.is_valid());
+    if agrees && unmapped {
+        info!("[kernel] mm self test passed");
+    } else {
+        error!(
+            "[kernel] mm self test failed: round trip {}, unmapped {}",
+            agrees, unmapped
+        );
+        panic!("mm self test failed");
+    }
+}
+
+#[allow(unused)]
+/// a page mapped to one frame and checked against another is caught, which
+/// is the kind of mistake `self_test` is there for
+pub fn self_test_broken_test() {
+    let mut kernel_space = KERNEL_SPACE.lock();
+    let start = VirtAddr::from(SELF_TEST_VA);
+    let end = VirtAddr::from(SELF_TEST_VA + PAGE_SIZE);
+    kernel_space.insert_framed_area(start, end, MapPermission::R | MapPermission::W);
+    let ppn = kernel_space.translate(start.floor()).unwrap().ppn();
+    let other = super::frame_alloc().unwrap();
+    assert!(round_trip_agrees(start, ppn));
+    assert!(!round_trip_agrees(start, other.ppn));
+    kernel_space.unmap(start.floor(), end.floor());
+    info!("self_test_broken_test passed!");
+}
+
+#[allow(unused)]
+/// a simple test for elf header validation
+pub fn elf_validation_test() {
+    let mut header = [0u8; 64];
+    header[..4].copy_from_slice(&[0x7f, 0x45, 0x4c, 0x46]);
+    header[4] = 2;
+    header[5] = 1;
+    header[16] = 2;
+    header[18] = 62; // EM_X86_64
+    assert!(MemorySet::from_elf(&header).is_err());
+    assert!(MemorySet::from_elf(&header[..20]).is_err());
+    header[0] = 0;
+    assert!(MemorySet::from_elf(&header).is_err());
+    info!("elf_validation_test passed!");
+}
+
+#[allow(unused)]
+/// frames of a user memory set return to the allocator once it is recycled
+pub fn recycle_test() {
+    let (mut memory_set, _, _, _) = MemorySet::from_elf(crate::loader::get_app_data(0)).unwrap();
+    let before = free_frame_count();
+    let released = memory_set.resident_pages() - 1;
+    let tables = memory_set.page_table.table_frames();
+    memory_set.unmap_all_user();
+    assert_eq!(memory_set.resident_pages(), 1);
+    let freed_tables = tables - memory_set.page_table.table_frames();
+    assert_eq!(free_frame_count(), before + released + freed_tables);
+    info!("recycle_test passed!");
+}
+
+#[allow(unused)]
+/// tearing down a user space frees the tables under the user areas along
+/// with their frames, keeps the trampoline and TrapContext mapped, and is
+/// safe to repeat
+pub fn unmap_all_user_test() {
+    let (mut memory_set, user_sp, entry_point, _) =
+        MemorySet::from_elf(crate::loader::get_app_data(0)).unwrap();
+    let before = free_frame_count();
+    let released = memory_set.resident_pages() - 1;
+    let tables = memory_set.page_table.table_frames();
+    memory_set.unmap_all_user();
+    let freed_tables = tables - memory_set.page_table.table_frames();
+    // the image and stack sit far below the trampoline, under their own tables
+    assert!(freed_tables > 0);
+    assert_eq!(free_frame_count(), before + released + freed_tables);
+    let mapped = |memory_set: &MemorySet, va: usize| {
+        memory_set
+            .translate(VirtAddr::from(va).floor())
+            .map_or(false, |pte| pte.is_valid())
+    };
+    assert!(!mapped(&memory_set, entry_point) && !mapped(&memory_set, user_sp - 1));
+    assert!(mapped(&memory_set, TRAMPOLINE) && mapped(&memory_set, TRAP_CONTEXT));
+    // nothing left to free the second time, and the set is still readable
+    let (after, tables_after) = (free_frame_count(), memory_set.page_table.table_frames());
+    memory_set.unmap_all_user();
+    assert_eq!(free_frame_count(), after);
+    assert_eq!(memory_set.page_table.table_frames(), tables_after);
+    assert_eq!(memory_set.resident_pages(), 1);
+    memory_set.report_unreleased(0);
+    info!("unmap_all_user_test passed!");
+}
+
+#[allow(unused)]
+/// an area mapped after the teardown of an exited task is reported with
+/// its frames, the TrapContext page left behind on purpose is not
+pub fn report_unreleased_leak_test() {
+    let (mut memory_set, _, _, _) = MemorySet::from_elf(crate::loader::get_app_data(0)).unwrap();
+    memory_set.unmap_all_user();
+    // a leak: frames mapped behind the teardown's back
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    memory_set.insert_framed_area(VirtAddr::from(0x1000), VirtAddr::from(0x3000), perm);
+    assert_eq!(
+        memory_set.unreleased_areas(),
+        [(VirtPageNum::from(1), VirtPageNum::from(3), 2)]
+    );
+    memory_set.report_unreleased(0);
+    info!("report_unreleased_leak_test passed!");
+}
+
+#[allow(unused)]
+/// a task torn down the normal way has nothing to report
+pub fn report_unreleased_clean_test() {
+    let (mut memory_set, _, _, _) = MemorySet::from_elf(crate::loader::get_app_data(0)).unwrap();
+    assert!(!memory_set.unreleased_areas().is_empty());
+    memory_set.unmap_all_user();
+    assert!(memory_set.unreleased_areas().is_empty());
+    info!("report_unreleased_clean_test passed!");
+}
+
+#[allow(unused)]
+/// iter_mappings yields exactly the resident pages of every area
+pub fn iter_mappings_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    memory_set.insert_framed_area(VirtAddr::from(0x3000), VirtAddr::from(0x5000), perm);
+    memory_set.insert_framed_area(VirtAddr::from(0x1000), VirtAddr::from(0x2000), perm);
+    memory_set.discard(VirtPageNum::from(4), VirtPageNum::from(5));
+    let mut mappings: Vec<_> = memory_set.iter_mappings().collect();
+    mappings.sort_by_key(|(vpn, _, _)| *vpn);
+    let mut expected = Vec::new();
+    for area in memory_set.areas.iter() {
+        for (vpn, frame) in area.data_frames.iter() {
+            expected.push((
+                *vpn,
+                frame.ppn,
+                PTEFlags::V | PTEFlags::R | PTEFlags::W | PTEFlags::U,
+            ));
+        }
+    }
+    expected.sort_by_key(|(vpn, _, _)| *vpn);
+    assert_eq!(mappings.len(), 2);
+    assert!(mappings == expected);
+    assert_eq!(mappings[0].0, VirtPageNum::from(1));
+    assert_eq!(mappings[1].0, VirtPageNum::from(3));
+    info!("iter_mappings_test passed!");
+}
+
+#[allow(unused)]
+/// a megapage takes one level-1 leaf instead of a level-2 table of 512 leaves
+pub fn huge_page_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    let start_va = VirtAddr::from(0x4000_0000);
+    let end_va = VirtAddr::from(0x4000_0000 + HUGE_PAGE_SIZE);
+    let table_frames = memory_set.page_table.table_frames();
+    assert!(memory_set.insert_huge_area(start_va, end_va, perm));
+    // only the level-1 table is new, the leaf lives in it
+    assert_eq!(memory_set.page_table.table_frames(), table_frames + 1);
+    assert_eq!(memory_set.resident_pages(), HUGE_PAGE_SIZE / PAGE_SIZE);
+    let base = memory_set.translate(start_va.floor()).unwrap().ppn();
+    assert_eq!(base.0 % (HUGE_PAGE_SIZE / PAGE_SIZE), 0);
+    let last = VirtPageNum(start_va.floor().0 + 511);
+    let pte = memory_set.translate(last).unwrap();
+    assert!(pte.is_valid() && pte.writable());
+    assert_eq!(pte.ppn().0, base.0 + 511);
+    pte.ppn().get_bytes_array()[0] = 0xab;
+    assert_eq!(PhysPageNum(base.0 + 511).get_bytes_array()[0], 0xab);
+    let free = free_frame_count();
+    memory_set.unmap(start_va.floor(), end_va.floor());
+    assert!(memory_set
+        .translate(last)
+        .map_or(true, |pte| !pte.is_valid()));
+    assert_eq!(free_frame_count(), free + HUGE_PAGE_SIZE / PAGE_SIZE);
+    info!("huge_page_test passed!");
+}
+
+#[allow(unused)]
+/// user copies cross page boundaries and fail as a whole on a bad page
+pub fn copy_user_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let rw = MapPermission::R | MapPermission::W | MapPermission::U;
+    memory_set.insert_framed_area(VirtAddr::from(0x1000), VirtAddr::from(0x4000), rw);
+    memory_set.insert_framed_area(
+        VirtAddr::from(0x5000),
+        VirtAddr::from(0x6000),
+        MapPermission::R | MapPermission::U,
+    );
+    let src: Vec<u8> = (0..64).collect();
+    assert_eq!(memory_set.copy_to_user(0x1fe0, &src), 64);
+    let mut dst = [0u8; 64];
+    assert_eq!(memory_set.copy_from_user(&mut dst, 0x1fe0), 64);
+    assert!(dst[..] == src[..]);
+    // a whole page in the middle
+    let page = [0x5au8; PAGE_SIZE + 2];
+    assert_eq!(memory_set.copy_to_user(0x1fff, &page), page.len() as isize);
+    let mut back = [0u8; PAGE_SIZE + 2];
+    assert_eq!(
+        memory_set.copy_from_user(&mut back, 0x1fff),
+        back.len() as isize
+    );
+    assert!(back[..] == page[..]);
+    // the copy would run into the unmapped page at 0x4000
+    memory_set.copy_to_user(0x3ff0, &[1u8; 16]);
+    assert_eq!(memory_set.copy_to_user(0x3ff0, &[2u8; 32]), EFAULT);
+    assert_eq!(memory_set.copy_from_user(&mut dst, 0x3ff0), EFAULT);
+    let mut head = [0u8; 16];
+    memory_set.copy_from_user(&mut head, 0x3ff0);
+    assert!(head == [1u8; 16]);
+    // read-only page
+    assert_eq!(memory_set.copy_to_user(0x5000, &[0u8; 4]), EFAULT);
+    assert_eq!(memory_set.copy_from_user(&mut head, 0x5000), 16);
+    info!("copy_user_test passed!");
+}
+
+#[allow(unused)]
+/// a lazy page faulted in after `protect` gets the new permission, and
+/// resident pages follow a later upgrade
+pub fn protect_lazy_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let r = MapPermission::R | MapPermission::U;
+    let rw = r | MapPermission::W;
+    let vpn = |va: usize| VirtAddr::from(va).floor();
+    memory_set.insert_lazy_area(VirtAddr::from(0x1000), VirtAddr::from(0x4000), rw);
+    memory_set.protect(vpn(0x2000), vpn(0x3000), r);
+    assert!(!memory_set.handle_page_fault(vpn(0x2000), MapPermission::W));
+    assert!(memory_set.handle_page_fault(vpn(0x2000), MapPermission::R));
+    let pte = memory_set.translate(vpn(0x2000)).unwrap();
+    assert!(pte.readable() && !pte.writable());
+    // the rest of the area keeps its permission
+    assert!(memory_set.handle_page_fault(vpn(0x1000), MapPermission::W));
+    assert!(memory_set.handle_page_fault(vpn(0x3000), MapPermission::W));
+    assert_eq!(memory_set.areas.len(), 3);
+    memory_set.protect(vpn(0x1000), vpn(0x4000), rw);
+    assert!(memory_set.translate(vpn(0x2000)).unwrap().writable());
+    assert_eq!(memory_set.resident_pages(), 3);
+    info!("protect_lazy_test passed!");
+}
+
+#[allow(unused)]
+/// areas inserted in any order are kept sorted, also across splits
+pub fn sorted_areas_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let rw = MapPermission::R | MapPermission::W | MapPermission::U;
+    let vpn = |va: usize| VirtAddr::from(va).floor();
+    memory_set.insert_framed_area(VirtAddr::from(0x5000), VirtAddr::from(0x6000), rw);
+    memory_set.insert_lazy_area(VirtAddr::from(0x1000), VirtAddr::from(0x2000), rw);
+    memory_set.insert_filled_area(VirtAddr::from(0x8000), VirtAddr::from(0xa000), rw, 1);
+    memory_set.insert_framed_area(VirtAddr::from(0x3000), VirtAddr::from(0x4000), rw);
+    let starts = |memory_set: &MemorySet| -> Vec<usize> {
+        memory_set
+            .areas
+            .iter()
+            .map(|area| area.vpn_range.get_start().0)
+            .collect()
+    };
+    assert_eq!(starts(&memory_set), [0x1, 0x3, 0x5, 0x8]);
+    memory_set.check_invariants();
+    memory_set.unmap(vpn(0x8000), vpn(0x9000));
+    memory_set.protect(
+        vpn(0x5000),
+        vpn(0x6000),
+        MapPermission::R | MapPermission::U,
+    );
+    memory_set.insert_framed_area(VirtAddr::from(0x6000), VirtAddr::from(0x8000), rw);
+    assert_eq!(starts(&memory_set), [0x1, 0x3, 0x5, 0x6, 0x9]);
+    memory_set.check_invariants();
+    info!("sorted_areas_test passed!");
+}
+
+#[allow(unused)]
+/// `coldest_page` prefers pages not accessed yet and never picks a locked one
+pub fn coldest_page_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let rw = MapPermission::R | MapPermission::W | MapPermission::U;
+    let vpn = |va: usize| VirtAddr::from(va).floor();
+    memory_set.insert_framed_area(VirtAddr::from(0x1000), VirtAddr::from(0x5000), rw);
+    // fresh ptes have no A bit, the first unlocked page wins
+    assert_eq!(memory_set.coldest_page(), Some(vpn(0x1000)));
+    memory_set.set_locked(vpn(0x1000), vpn(0x3000), true);
+    for _ in 0..2 {
+        let cold = memory_set.coldest_page().unwrap();
+        assert!(cold >= vpn(0x3000));
+    }
+    memory_set.set_locked(vpn(0x3000), vpn(0x5000), true);
+    assert_eq!(memory_set.coldest_page(), None);
+    memory_set.set_locked(vpn(0x2000), vpn(0x3000), false);
+    assert_eq!(memory_set.coldest_page(), Some(vpn(0x2000)));
+    // unmapping forgets the locks of the range
+    memory_set.unmap(vpn(0x3000), vpn(0x5000));
+    memory_set.insert_framed_area(VirtAddr::from(0x3000), VirtAddr::from(0x4000), rw);
+    memory_set.set_locked(vpn(0x2000), vpn(0x3000), true);
+    assert_eq!(memory_set.coldest_page(), Some(vpn(0x3000)));
+    info!("coldest_page_test passed!");
+}
+
+#[allow(unused)]
+/// a token is only valid while its page table lives and its fields are intact
+pub fn satp_check_test() {
+    assert!(super::current_satp_is_valid());
+    let memory_set = MemorySet::new_bare();
+    let token = memory_set.token();
+    assert!(super::satp_is_valid(token));
+    // wrong mode, then a root ppn that is not a page table
+    assert!(!super::satp_is_valid(token & !(0xf << 60)));
+    assert!(!super::satp_is_valid(token ^ (1 << 20)));
+    drop(memory_set);
+    assert!(!super::satp_is_valid(token));
+    info!("satp_check_test passed!");
+}
+
+#[allow(unused)]
+/// every map syscall range check refuses a length of 0 and a wrapping range
+pub fn user_range_test() {
+    assert_eq!(user_range(0x1000_0000, 0), Err(EINVAL));
+    assert_eq!(user_range(0x1000_0001, 0x1000), Err(EINVAL));
+    assert_eq!(user_range(usize::MAX & !0xfff, 0x2000), Err(EINVAL));
+    let (start, end) = user_range(0x1000_0000, 0x1001).unwrap();
+    assert_eq!((start.0, end.0), (0x1000_0000, 0x1000_1001));
+    assert_eq!(MapPermission::from_port(0), None);
+    assert_eq!(MapPermission::from_port(8 | 1), None);
+    assert_eq!(
+        MapPermission::from_port(3),
+        Some(MapPermission::R | MapPermission::W | MapPermission::U)
+    );
+    info!("user_range_test passed!");
+}
+
+#[allow(unused)]
+/// tokens and the satp in use select Sv39, and a vpn splits into one index per level
+pub fn satp_mode_test() {
+    use super::page_table::{PAGE_TABLE_LEVELS, VA_WIDTH_SV39};
+    let memory_set = MemorySet::new_bare();
+    assert_eq!(satp_mode(memory_set.token()), SATP_MODE_SV39);
+    assert_eq!(satp_mode(KERNEL_SPACE.lock().token()), SATP_MODE_SV39);
+    assert_eq!(satp_mode(satp::read().bits()), SATP_MODE_SV39);
+    let top_vpn = VirtAddr::from((1 << VA_WIDTH_SV39) - 1).floor();
+    assert_eq!(top_vpn.indexes(), [511; PAGE_TABLE_LEVELS]);
+    info!("satp_mode_test passed!");
+}
+
+#[allow(unused)]
+/// a blob copied in mid-page fills exactly its bytes; the rest of the pages
+/// it touches reads as zero even if the frames held other data
+pub fn copy_data_tail_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let start = VirtAddr::from(0x1000_0000);
+    let mut area = MapArea::new(
+        start,
+        VirtAddr::from(start.0 + 3 * PAGE_SIZE),
+        MapType::Framed,
+        MapPermission::R | MapPermission::U,
+    );
+    area.map(&mut memory_set.page_table);
+    let page = |memory_set: &MemorySet, i: usize| {
+        memory_set
+            .page_table
+            .translate(VirtPageNum(start.floor().0 + i))
+            .unwrap()
+            .ppn()
+            .get_bytes_array()
+    };
+    for i in 0..3 {
+        page(&memory_set, i).fill(0xff);
+    }
+    let offset = 100;
+    let blob: Vec<u8> = (0..PAGE_SIZE + 1000).map(|i| (i % 251) as u8).collect();
+    area.copy_data(&memory_set.page_table, offset, &blob);
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(page(&memory_set, 0));
+    bytes.extend_from_slice(page(&memory_set, 1));
+    assert!(bytes[..offset].iter().all(|&b| b == 0));
+    assert_eq!(&bytes[offset..offset + blob.len()], &blob[..]);
+    assert!(bytes[offset + blob.len()..].iter().all(|&b| b == 0));
+    // a page the data does not reach is left alone
+    assert!(page(&memory_set, 2).iter().all(|&b| b == 0xff));
+    memory_set.areas.push(area);
+    info!("copy_data_tail_test passed!");
+}
+
+#[allow(unused)]
+/// mapping a range into a fresh address space takes no more table frames
+/// than `table_frames_for` allows, which is exact for a range crossing a
+/// leaf table boundary
+pub fn table_frames_for_test() {
+    use super::table_frames_for;
+    let mut memory_set = MemorySet::new_bare();
+    let start = VirtAddr::from(HUGE_PAGE_SIZE - PAGE_SIZE);
+    let end = VirtAddr::from(HUGE_PAGE_SIZE + PAGE_SIZE);
+    let tables = table_frames_for(start.floor(), end.floor());
+    assert_eq!(tables, 3);
+    assert_eq!(table_frames_for(start.floor(), start.floor()), 0);
+    let before = free_frame_count();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    memory_set.insert_framed_area(start, end, perm);
+    assert_eq!(before - free_frame_count(), 2 + tables);
+    info!("table_frames_for_test passed!");
+}
+
+#[allow(unused)]
+/// after `unmap` a kernel range reads through its new frames rather than a
+/// stale translation, relying on the flush `unmap` does itself, both for a
+/// short range flushed page by page and a long one flushed all at once
+pub fn tlb_flush_range_test() {
+    use super::frame_alloc;
+    use crate::config::TLB_FLUSH_ALL_THRESHOLD;
+    // well clear of the identity map and the kernel stacks
+    let base = 0x1_0000_0000usize;
+    for pages in [1, TLB_FLUSH_ALL_THRESHOLD + 1] {
+        // which way a range is flushed, on one that is never mapped
+        let spare = VirtAddr::from(base + 0x1000_0000).floor();
+        assert_eq!(
+            flush_tlb_range(spare, VirtPageNum(spare.0 + pages)),
+            pages > TLB_FLUSH_ALL_THRESHOLD
+        );
+        let start = VirtAddr::from(base);
+        let end = VirtAddr::from(base + pages * PAGE_SIZE);
+        let mut kernel_space = KERNEL_SPACE.lock();
+        kernel_space.insert_framed_area(start, end, MapPermission::R | MapPermission::W);
+        let page = |i: usize| (base + i * PAGE_SIZE) as *mut usize;
+        let old_ppns: Vec<usize> = (0..pages)
+            .map(|i| {
+                // load the translation into the TLB
+                unsafe { page(i).write_volatile(0xdead_beef) };
+                kernel_space
+                    .translate(VirtPageNum(start.floor().0 + i))
+                    .unwrap()
+                    .ppn()
+                    .0
+            })
+            .collect();
+        kernel_space.unmap(start.floor(), end.floor());
+        // keep the old frames from coming straight back to the same pages
+        let held: Vec<_> = (0..pages).map(|_| frame_alloc().unwrap()).collect();
+        kernel_space.insert_framed_area(start, end, MapPermission::R | MapPermission::W);
+        for i in 0..pages {
+            let ppn = kernel_space
+                .translate(VirtPageNum(start.floor().0 + i))
+                .unwrap()
+                .ppn()
+                .0;
+            assert!(!old_ppns.contains(&ppn));
+            assert_eq!(unsafe { page(i).read_volatile() }, 0);
+        }
+        kernel_space.unmap(start.floor(), end.floor());
+        drop(held);
+    }
+    info!("tlb_flush_range_test passed!");
+}
+
+#[allow(unused)]
+/// two sets built the same way only differ in the page written to, and a
+/// lazy page counts once it is faulted in on one side
+pub fn memory_set_diff_test() {
+    let build = || {
+        let mut memory_set = MemorySet::new_bare();
+        memory_set.insert_filled_area(
+            VirtAddr::from(0x1000_0000),
+            VirtAddr::from(0x1000_4000),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+            0x5a,
+        );
+        memory_set.insert_lazy_area(
+            VirtAddr::from(0x2000_0000),
+            VirtAddr::from(0x2000_2000),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        memory_set
+    };
+    let snapshot = build();
+    let mut modified = build();
+    assert!(snapshot.diff(&modified).is_empty());
+    let written = VirtPageNum(0x1000_2);
+    modified.translate(written).unwrap().ppn().get_bytes_array()[100] = 0;
+    assert_eq!(snapshot.diff(&modified), [written]);
+    let faulted = VirtPageNum(0x2000_1);
+    assert!(modified.handle_page_fault(faulted, MapPermission::R));
+    assert_eq!(modified.diff(&snapshot), [written, faulted]);
+    info!("memory_set_diff_test passed!");
+}
@@ -14,20 +14,78 @@ mod page_table;
 
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 pub use address::{StepByOne, VPNRange};
-pub use frame_allocator::{frame_alloc, FrameTracker};
+use core::ops::Range;
+#[cfg(feature = "frame_fuzz")]
+pub use frame_allocator::frame_allocator_fuzz;
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_filled, frame_alloc_uninit, frame_in_range, frame_stats,
+    free_frame_count, huge_frame_alloc, huge_frame_alloc_compacting, memory_end, FrameTracker,
+    HugeFrameTracker,
+};
+pub use heap_allocator::heap_stats;
 pub use memory_set::remap_test;
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, PageTableEntry};
-use page_table::{PTEFlags, PageTable};
+#[cfg(feature = "mm_self_test")]
+pub use memory_set::self_test;
+pub use memory_set::{user_range, MapPermission, MemorySet, KERNEL_SPACE};
+pub use page_table::{current_satp_is_valid, satp_is_valid, PageTableEntry};
+use page_table::{flush_tlb_range, PTEFlags, PageTable};
+pub use page_table::{satp_mode, table_frames_for, SATP_MODE_SV39};
 
-/// initiate heap allocator, frame allocator and kernel space
-pub fn init() {
+/// initiate heap allocator, frame allocator and kernel space, given where
+/// the RAM found at boot ends if it was found
+pub fn init(ram_end: Option<usize>) {
     heap_allocator::init_heap();
-    frame_allocator::init_frame_allocator();
+    frame_allocator::init_frame_allocator(ram_end);
+    print_memory_layout();
     KERNEL_SPACE.lock().activate();
 }
 
-pub fn translate_by_token(token: usize, va: VirtAddr) -> Option<PhysAddr> {
-    let ppn = PageTable::from_token(token).translate(va.floor())?.ppn();
-    Some(PhysAddr::from(PhysAddr::from(ppn).0 + va.page_offset()))
+/// The kernel image, the kernel heap and the frames, by name. The heap is
+/// part of the image; the frames must be clear of both.
+fn memory_layout() -> [(&'static str, Range<usize>); 3] {
+    extern "C" {
+        fn skernel();
+        fn ekernel();
+    }
+    let frames_start: usize = PhysAddr::from(ekernel as usize).ceil().into();
+    let frames_end: usize = PhysAddr::from(memory_end()).floor().into();
+    [
+        ("kernel image", skernel as usize..ekernel as usize),
+        ("kernel heap", heap_allocator::heap_range()),
+        ("frames", frames_start..frames_end),
+    ]
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Print where the physical memory regions lie, flagging the frames if they
+/// run into the image or the heap.
+pub fn print_memory_layout() {
+    let layout = memory_layout();
+    for (name, range) in layout.iter() {
+        println!(
+            "[kernel] {:>12}: [{:#x}, {:#x})",
+            name, range.start, range.end
+        );
+    }
+    let (frames_name, frames) = &layout[2];
+    for (name, range) in layout[..2].iter() {
+        if overlaps(frames, range) {
+            error!("[kernel] {} overlap the {}", frames_name, name);
+        }
+    }
+}
+
+#[allow(unused)]
+/// the frames are disjoint from the image and the heap, and the heap lies
+/// within the image
+pub fn memory_layout_test() {
+    let [(_, image), (_, heap), (_, frames)] = memory_layout();
+    assert!(!overlaps(&frames, &image));
+    assert!(!overlaps(&frames, &heap));
+    assert!(image.start <= heap.start && heap.end <= image.end);
+    assert!(overlaps(&(0..2), &(1..3)) && !overlaps(&(0..1), &(1..2)));
+    info!("memory_layout_test passed!");
 }
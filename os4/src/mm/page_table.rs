@@ -1,9 +1,41 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
-use super::{frame_alloc, FrameTracker, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use super::{frame_alloc, FrameTracker, PhysPageNum, VirtPageNum};
+use crate::config::{PAGE_SIZE_BITS, TLB_FLUSH_ALL_THRESHOLD};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeSet;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
+use lazy_static::*;
+use riscv::register::satp;
+
+/// `satp.MODE` value selecting Sv39
+pub const SATP_MODE_SV39: usize = 8;
+const SATP_MODE_SHIFT: usize = 60;
+/// width of `satp.PPN`
+const SATP_PPN_BITS: usize = 44;
+/// levels of a page table, from the root down to the 4K leaves
+pub const PAGE_TABLE_LEVELS: usize = 3;
+/// bits of the vpn indexing one level, for 512 entries per node
+pub const VPN_INDEX_BITS: usize = 9;
+/// width of a virtual address
+pub const VA_WIDTH_SV39: usize = 39;
+
+// the table shape has to give Sv39 addresses; moving to Sv48 means
+// changing the mode, the levels and the width together
+const _: () = assert!(PAGE_TABLE_LEVELS * VPN_INDEX_BITS + PAGE_SIZE_BITS == VA_WIDTH_SV39);
+
+/// The `satp.MODE` field of `satp`.
+pub fn satp_mode(satp: usize) -> usize {
+    satp >> SATP_MODE_SHIFT
+}
+
+lazy_static! {
+    /// root ppns of every page table that is still alive
+    static ref LIVE_ROOTS: UPSafeCell<BTreeSet<usize>> =
+        unsafe { UPSafeCell::new(BTreeSet::new()) };
+}
 
 bitflags! {
     /// page table entry flags
@@ -53,6 +85,11 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// A valid entry with any of `R W X` set maps memory instead of a next-level table.
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid()
+            && (self.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X)) != PTEFlags::empty()
+    }
 }
 
 /// page table structure
@@ -61,23 +98,82 @@ pub struct PageTable {
     frames: Vec<FrameTracker>,
 }
 
+impl Drop for PageTable {
+    fn drop(&mut self) {
+        LIVE_ROOTS.exclusive_access().remove(&self.root_ppn.0);
+    }
+}
+
+/// Whether `satp` selects Sv39 and a page table that has not been dropped.
+pub fn satp_is_valid(satp: usize) -> bool {
+    satp_mode(satp) == SATP_MODE_SV39
+        && LIVE_ROOTS
+            .exclusive_access()
+            .contains(&(satp & ((1usize << SATP_PPN_BITS) - 1)))
+}
+
+/// Check the satp currently in use, see [`satp_is_valid`].
+pub fn current_satp_is_valid() -> bool {
+    satp_is_valid(satp::read().bits())
+}
+
+/// Most table frames mapping `[start_vpn, end_vpn)` can allocate: one for
+/// each node below the root the range passes through, as if none existed.
+pub fn table_frames_for(start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> usize {
+    if start_vpn.0 >= end_vpn.0 {
+        return 0;
+    }
+    (1..PAGE_TABLE_LEVELS)
+        .map(|level| {
+            let shift = level * VPN_INDEX_BITS;
+            ((end_vpn.0 - 1) >> shift) - (start_vpn.0 >> shift) + 1
+        })
+        .sum()
+}
+
+/// Drop stale translations of `[start_vpn, end_vpn)` from the TLB: one
+/// `sfence.vma` per page for a short range, a single global one past
+/// `TLB_FLUSH_ALL_THRESHOLD` pages. Returns whether the whole TLB was flushed.
+pub fn flush_tlb_range(start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+    let pages = end_vpn.0.saturating_sub(start_vpn.0);
+    if pages > TLB_FLUSH_ALL_THRESHOLD {
+        unsafe { core::arch::asm!("sfence.vma") };
+        return true;
+    }
+    for vpn in start_vpn.0..end_vpn.0 {
+        unsafe { core::arch::asm!("sfence.vma {}, zero", in(reg) vpn << PAGE_SIZE_BITS) };
+    }
+    false
+}
+
+/// Unlink the empty tables below the table at `ppn`, which sits at `level`
+/// counting from the root, collecting their frames. Returns whether the
+/// table at `ppn` is left without a valid entry.
+fn prune_empty_tables(ppn: PhysPageNum, level: usize, freed: &mut Vec<PhysPageNum>) -> bool {
+    for pte in ppn.get_pte_array().iter_mut() {
+        // entries of a last-level table are leaves
+        if level < 2
+            && pte.is_valid()
+            && !pte.is_leaf()
+            && prune_empty_tables(pte.ppn(), level + 1, freed)
+        {
+            freed.push(pte.ppn());
+            *pte = PageTableEntry::empty();
+        }
+    }
+    ppn.get_pte_array().iter().all(|pte| !pte.is_valid())
+}
+
 /// Assume that it won't oom when creating/mapping.
 impl PageTable {
     pub fn new() -> Self {
         let frame = frame_alloc().unwrap();
+        LIVE_ROOTS.exclusive_access().insert(frame.ppn.0);
         PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
         }
     }
-    /// Temporarily used to get arguments from user space.
-    /// 临时从用户空间获取参数
-    pub fn from_token(satp: usize) -> Self {
-        Self {
-            root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
-            frames: Vec::new(),
-        }
-    }
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         let mut idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
@@ -88,6 +184,7 @@ impl PageTable {
                 result = Some(pte);
                 break;
             }
+            assert!(!pte.is_leaf(), "vpn {:?} is inside a huge page", vpn);
             if !pte.is_valid() {
                 let frame = frame_alloc().unwrap();
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
@@ -126,33 +223,62 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
-    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).copied()
+    /// Find the level-1 entry covering `vpn`, creating the level-1 table if needed.
+    fn find_huge_pte_create(&mut self, vpn: VirtPageNum) -> &mut PageTableEntry {
+        let idxs = vpn.indexes();
+        let pte = &mut self.root_ppn.get_pte_array()[idxs[0]];
+        if !pte.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        &mut pte.ppn().get_pte_array()[idxs[1]]
     }
-    pub fn token(&self) -> usize {
-        8usize << 60 | self.root_ppn.0
+    fn find_huge_pte(&self, vpn: VirtPageNum) -> Option<&PageTableEntry> {
+        let idxs = vpn.indexes();
+        let pte = &self.root_ppn.get_pte_array()[idxs[0]];
+        if !pte.is_valid() {
+            return None;
+        }
+        Some(&pte.ppn().get_pte_array()[idxs[1]])
     }
-}
-
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-    let page_table = PageTable::from_token(token);
-    let mut start = ptr as usize;
-    let end = start + len;
-    let mut v = Vec::new();
-    while start < end {
-        let start_va = VirtAddr::from(start);
-        let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
-        vpn.step();
-        let mut end_va: VirtAddr = vpn.into();
-        end_va = end_va.min(VirtAddr::from(end));
-        if end_va.page_offset() == 0 {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
-        } else {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+    /// Map the 2MB megapage at `vpn` straight to `ppn` with a level-1 leaf.
+    /// Both must be aligned to 512 pages.
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        assert!(vpn.0 % 512 == 0 && ppn.0 % 512 == 0);
+        let pte = self.find_huge_pte_create(vpn);
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+    pub fn unmap_huge(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_huge_pte_create(vpn);
+        assert!(pte.is_leaf(), "vpn {:?} is not a huge page", vpn);
+        *pte = PageTableEntry::empty();
+    }
+    /// Entry mapping `vpn`. Inside a megapage the returned entry carries the
+    /// ppn of that particular 4KB page.
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        match self.find_huge_pte(vpn) {
+            Some(pte) if pte.is_leaf() => Some(PageTableEntry::new(
+                PhysPageNum(pte.ppn().0 + vpn.indexes()[2]),
+                pte.flags(),
+            )),
+            _ => self.find_pte(vpn).copied(),
         }
-        start = end_va.into();
     }
-    v
+    /// Number of frames holding page-table nodes.
+    pub fn table_frames(&self) -> usize {
+        self.frames.len()
+    }
+    /// Free every table below the root that no longer maps anything,
+    /// returning how many frames went back to the allocator.
+    pub fn free_empty_tables(&mut self) -> usize {
+        let mut freed = Vec::new();
+        prune_empty_tables(self.root_ppn, 0, &mut freed);
+        self.frames.retain(|frame| !freed.contains(&frame.ppn));
+        freed.len()
+    }
+    pub fn token(&self) -> usize {
+        SATP_MODE_SV39 << SATP_MODE_SHIFT | self.root_ppn.0
+    }
 }
@@ -1,10 +1,30 @@
 #![allow(unused)]
 
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
 const SBI_SHUTDOWN: usize = 8;
 
+const SBI_EXT_BASE: usize = 0x10;
+const SBI_BASE_PROBE_EXTENSION: usize = 3;
+/// debug console extension, writes a whole buffer in one call
+const SBI_EXT_DBCN: usize = 0x4442_434e;
+const SBI_DBCN_CONSOLE_WRITE: usize = 0;
+/// system reset extension
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SBI_SRST_SYSTEM_RESET: usize = 0;
+const SBI_SRST_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_TYPE_COLD_REBOOT: usize = 1;
+const SBI_SRST_REASON_NONE: usize = 0;
+const SBI_SRST_REASON_FAILURE: usize = 1;
+
+/// SBI calls made to put bytes on the console
+static CONSOLE_CALLS: AtomicUsize = AtomicUsize::new(0);
+/// whether the SBI has the debug console: 0 not probed yet, 1 yes, 2 no
+static DBCN_STATE: AtomicU8 = AtomicU8::new(0);
+
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
@@ -21,19 +41,104 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+/// Call function `fid` of extension `eid`, returning the error and value.
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") arg0 => error,
+            inlateout("x11") arg1 => value,
+            in("x12") arg2,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    (error, value)
+}
+
+fn has_dbcn() -> bool {
+    match DBCN_STATE.load(Ordering::Relaxed) {
+        0 => {
+            let (error, value) =
+                sbi_call_ext(SBI_EXT_BASE, SBI_BASE_PROBE_EXTENSION, SBI_EXT_DBCN, 0, 0);
+            let present = error == 0 && value != 0;
+            DBCN_STATE.store(if present { 1 } else { 2 }, Ordering::Relaxed);
+            present
+        }
+        state => state == 1,
+    }
+}
+
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
 }
 
 pub fn console_putchar(c: usize) {
+    CONSOLE_CALLS.fetch_add(1, Ordering::Relaxed);
     sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
 }
 
+/// Put `bytes` on the console, in one call per chunk the SBI accepts if it
+/// has the debug console and one per byte otherwise. `bytes` must be
+/// identity mapped, as the SBI takes a physical address.
+pub fn console_write(bytes: &[u8]) {
+    if !has_dbcn() {
+        bytes.iter().for_each(|&c| console_putchar(c as usize));
+        return;
+    }
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        CONSOLE_CALLS.fetch_add(1, Ordering::Relaxed);
+        let (error, written) = sbi_call_ext(
+            SBI_EXT_DBCN,
+            SBI_DBCN_CONSOLE_WRITE,
+            rest.len(),
+            rest.as_ptr() as usize,
+            0,
+        );
+        if error != 0 {
+            rest.iter().for_each(|&c| console_putchar(c as usize));
+            return;
+        }
+        rest = &rest[written.min(rest.len())..];
+    }
+}
+
+/// How many SBI calls have put bytes on the console so far.
+pub fn console_calls() -> usize {
+    CONSOLE_CALLS.load(Ordering::Relaxed)
+}
+
 pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
 pub fn shutdown() -> ! {
+    crate::console::flush();
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
+
+/// Reset the system with the SRST extension. Only returns if the SBI lacks
+/// it or refused the reset.
+fn system_reset(reset_type: usize, reason: usize) {
+    crate::console::flush();
+    sbi_call_ext(SBI_EXT_SRST, SBI_SRST_SYSTEM_RESET, reset_type, reason, 0);
+}
+
+/// Power off reporting a system failure, so the emulator exits non-zero.
+/// Falls back to a plain shutdown on an SBI without SRST.
+pub fn shutdown_failure() -> ! {
+    system_reset(SBI_SRST_TYPE_SHUTDOWN, SBI_SRST_REASON_FAILURE);
+    sbi_call(SBI_SHUTDOWN, 0, 0, 0);
+    unreachable!("It should shutdown!");
+}
+
+/// Cold reboot, falling back to a plain shutdown on an SBI without SRST.
+pub fn reboot() -> ! {
+    system_reset(SBI_SRST_TYPE_COLD_REBOOT, SBI_SRST_REASON_NONE);
+    sbi_call(SBI_SHUTDOWN, 0, 0, 0);
+    unreachable!("It should reboot!");
+}
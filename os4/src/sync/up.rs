@@ -28,4 +28,22 @@ impl<T> UPSafeCell<T> {
     pub fn exclusive_access(&self) -> RefMut<'_, T> {
         self.inner.borrow_mut()
     }
+    /// Like `exclusive_access`, but `None` if the data is already borrowed,
+    /// for code that only wants a look when it is safe to, e.g. a watchdog.
+    pub fn try_exclusive_access(&self) -> Option<RefMut<'_, T>> {
+        self.inner.try_borrow_mut().ok()
+    }
+}
+
+#[allow(unused)]
+/// `try_exclusive_access` gives up while a borrow is held, and succeeds again
+/// once it is dropped
+pub fn try_exclusive_access_test() {
+    let cell = unsafe { UPSafeCell::new(0usize) };
+    let held = cell.exclusive_access();
+    assert!(cell.try_exclusive_access().is_none());
+    drop(held);
+    *cell.try_exclusive_access().unwrap() += 1;
+    assert_eq!(*cell.exclusive_access(), 1);
+    info!("try_exclusive_access_test passed!");
 }
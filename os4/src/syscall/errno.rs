@@ -0,0 +1,38 @@
+//! Error numbers shared by all syscalls
+//!
+//! A failing syscall returns one of these negated error numbers, using the
+//! same values as Linux. `sys_mmap`, `sys_munmap` and `sys_set_priority` are
+//! fixed by the lab ABI to return `-1` on any failure, so they only use these
+//! internally and for logging.
+
+/// no such task
+pub const ESRCH: isize = -3;
+/// out of memory
+pub const ENOMEM: isize = -12;
+/// bad address
+pub const EFAULT: isize = -14;
+/// already exists
+pub const EEXIST: isize = -17;
+/// invalid argument
+pub const EINVAL: isize = -22;
+/// too many open files
+pub const EMFILE: isize = -24;
+/// write to a pipe with no read end open
+pub const EPIPE: isize = -32;
+/// function not implemented
+pub const ENOSYS: isize = -38;
+
+/// Name of an error number, for logging.
+pub fn errno_str(code: isize) -> &'static str {
+    match code {
+        ESRCH => "ESRCH",
+        ENOMEM => "ENOMEM",
+        EFAULT => "EFAULT",
+        EEXIST => "EEXIST",
+        EINVAL => "EINVAL",
+        EMFILE => "EMFILE",
+        EPIPE => "EPIPE",
+        ENOSYS => "ENOSYS",
+        _ => "unknown error",
+    }
+}
@@ -1,21 +1,247 @@
 //! File and filesystem-related syscalls
 
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
+use super::errno::{EFAULT, EINVAL, EPIPE};
+use crate::config::{PIPE_BUFFER_SIZE, TRAP_CONTEXT};
+use crate::fs::{pipe_create, pipe_exists, pipe_read, pipe_write, FileDescriptor};
+use crate::mm::MapPermission;
+use crate::sbi::console_getchar;
+use crate::task::{
+    close_current_fd, copy_from_current_user, copy_to_current_user, dup_current_fd, get_current_fd,
+    open_current_fd, prepare_current_user, suspend_current_and_run_next,
+};
+use alloc::vec;
 
-const FD_STDOUT: usize = 1;
+/// most segments `sys_writev` takes in one call
+const IOV_MAX: usize = 1024;
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for usebuffer in buffers {
-                print!("{}", core::str::from_utf8(usebuffer).unwrap());
+/// one segment of a `sys_writev` buffer in user space
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+/// Bytes a write copies out of user space at a time.
+const WRITE_CHUNK_SIZE: usize = 256;
+
+/// `sys_pipe_open` end: the read end
+const PIPE_END_READ: usize = 0;
+/// `sys_pipe_open` end: the write end
+const PIPE_END_WRITE: usize = 1;
+
+/// What `fd` of the current task refers to, if it is open for writing.
+fn writable(fd: usize) -> Option<FileDescriptor> {
+    get_current_fd(fd).filter(|file| file.writable())
+}
+
+/// Write all of `buffer` to `file`, waiting by yielding while a pipe is full.
+/// Returns the number of bytes written, short only if a pipe lost its reader.
+fn write_file(file: FileDescriptor, buffer: &[u8]) -> isize {
+    match file {
+        FileDescriptor::PipeWrite(id) => {
+            let mut written = 0;
+            while written < buffer.len() {
+                match pipe_write(id, &buffer[written..]) {
+                    Some(0) if written == 0 => return EPIPE,
+                    Some(0) => break,
+                    Some(count) => written += count,
+                    None => suspend_current_and_run_next(),
+                }
             }
-            len as isize
+            written as isize
         }
         _ => {
-            panic!("Unsupported fd in sys_write!");
+            // a chunk may end in the middle of a character
+            crate::console::write_bytes(buffer);
+            buffer.len() as isize
+        }
+    }
+}
+
+/// Write `len` bytes at `src_va`, which must have been prepared, to `file`
+/// through a buffer of `WRITE_CHUNK_SIZE` bytes. Returns the number of bytes
+/// written, short if a pipe lost its reader, or the error if none were.
+fn write_from_user(file: FileDescriptor, src_va: usize, len: usize) -> isize {
+    let mut chunk = [0u8; WRITE_CHUNK_SIZE];
+    let mut written = 0;
+    while written < len {
+        let count = (len - written).min(WRITE_CHUNK_SIZE);
+        let result = match copy_from_current_user(&mut chunk[..count], src_va + written) {
+            err if err < 0 => err,
+            _ => write_file(file, &chunk[..count]),
+        };
+        if result < 0 && written == 0 {
+            return result;
+        }
+        if result < 0 {
+            break;
+        }
+        written += result as usize;
+        if (result as usize) < count {
+            break;
+        }
+    }
+    written as isize
+}
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    let file = match writable(fd) {
+        Some(file) => file,
+        None => return EINVAL,
+    };
+    if !prepare_current_user(buf as usize, len, MapPermission::R) {
+        return EFAULT;
+    }
+    write_from_user(file, buf as usize, len)
+}
+
+/// Read from `fd`, waiting by yielding until there is something to read.
+/// Stdin gives one byte at a time, a pipe as many as it holds. Returns the
+/// number of bytes read, 0 at the end of a pipe.
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let file = match get_current_fd(fd).filter(|file| file.readable()) {
+        Some(file) => file,
+        None => return EINVAL,
+    };
+    if len == 0 {
+        return 0;
+    }
+    if let FileDescriptor::PipeRead(id) = file {
+        // bytes taken from the pipe could not be put back on a bad buffer
+        if !prepare_current_user(buf as usize, len, MapPermission::W) {
+            return EFAULT;
+        }
+        let mut buffer = vec![0u8; len.min(PIPE_BUFFER_SIZE)];
+        let count = loop {
+            match pipe_read(id, &mut buffer) {
+                Some(count) => break count,
+                None => suspend_current_and_run_next(),
+            }
+        };
+        return match copy_to_current_user(buf as usize, &buffer[..count]) {
+            err if err < 0 => err,
+            _ => count as isize,
+        };
+    }
+    // a key read into a bad buffer would be lost
+    if !prepare_current_user(buf as usize, 1, MapPermission::W) {
+        return EFAULT;
+    }
+    // a prompt without a newline is still in the console buffer
+    crate::console::flush();
+    let c = loop {
+        // SBI returns usize::MAX while no key is pending
+        match console_getchar() {
+            c if c > u8::MAX as usize => suspend_current_and_run_next(),
+            c => break c as u8,
+        }
+    };
+    match copy_to_current_user(buf as usize, &[c]) {
+        err if err < 0 => err,
+        _ => 1,
+    }
+}
+
+/// Duplicate `fd` onto the lowest free fd, which is returned.
+pub fn sys_dup(fd: usize) -> isize {
+    dup_current_fd(fd)
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    close_current_fd(fd)
+}
+
+/// Create a pipe and store its read and write fds to `fds[0]` and `fds[1]`.
+pub fn sys_pipe(fds: *mut [usize; 2]) -> isize {
+    let id = pipe_create();
+    let read_fd = open_current_fd(FileDescriptor::PipeRead(id));
+    if read_fd < 0 {
+        return read_fd;
+    }
+    let write_fd = open_current_fd(FileDescriptor::PipeWrite(id));
+    if write_fd < 0 {
+        close_current_fd(read_fd as usize);
+        return write_fd;
+    }
+    let pipe_fds = [read_fd as usize, write_fd as usize];
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            pipe_fds.as_ptr() as *const u8,
+            core::mem::size_of_val(&pipe_fds),
+        )
+    };
+    match copy_to_current_user(fds as usize, bytes) {
+        err if err < 0 => {
+            close_current_fd(read_fd as usize);
+            close_current_fd(write_fd as usize);
+            err
+        }
+        _ => 0,
+    }
+}
+
+/// Open one end of pipe `id`, numbered in creation order from 0, on a new fd.
+/// This is how a task gets at a pipe another task created, as there is no
+/// fork to inherit it through.
+pub fn sys_pipe_open(id: usize, end: usize) -> isize {
+    if !pipe_exists(id) {
+        return EINVAL;
+    }
+    match end {
+        PIPE_END_READ => open_current_fd(FileDescriptor::PipeRead(id)),
+        PIPE_END_WRITE => open_current_fd(FileDescriptor::PipeWrite(id)),
+        _ => EINVAL,
+    }
+}
+
+/// Write the `iovcnt` segments described at `iov` in order, returning the
+/// total length. Every segment is checked before anything is written, and a
+/// total too large for the return value is `EINVAL`.
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    let file = match writable(fd) {
+        Some(file) if iovcnt <= IOV_MAX => file,
+        _ => return EINVAL,
+    };
+    let mut iovs = vec![IoVec { base: 0, len: 0 }; iovcnt];
+    let iov_bytes = unsafe {
+        core::slice::from_raw_parts_mut(
+            iovs.as_mut_ptr() as *mut u8,
+            iovcnt * core::mem::size_of::<IoVec>(),
+        )
+    };
+    if copy_from_current_user(iov_bytes, iov as usize) < 0 {
+        return EFAULT;
+    }
+    if iovs.iter().any(|iov| {
+        iov.base
+            .checked_add(iov.len)
+            .map_or(true, |end| end > TRAP_CONTEXT)
+    }) {
+        return EFAULT;
+    }
+    let total = iovs
+        .iter()
+        .try_fold(0usize, |total, iov| total.checked_add(iov.len));
+    if total.map_or(true, |total| total > isize::MAX as usize) {
+        return EINVAL;
+    }
+    if !iovs
+        .iter()
+        .all(|iov| prepare_current_user(iov.base, iov.len, MapPermission::R))
+    {
+        return EFAULT;
+    }
+    let mut written = 0;
+    for iov in iovs {
+        let result = write_from_user(file, iov.base, iov.len);
+        if result < 0 {
+            return if written == 0 { result } else { written };
+        }
+        written += result;
+        if (result as usize) < iov.len {
+            break;
         }
     }
+    written
 }
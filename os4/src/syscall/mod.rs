@@ -10,33 +10,102 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+const SYSCALL_DUP: usize = 24;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_WRITEV: usize = 66;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_SCHED_GETPARAM: usize = 121;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MLOCK: usize = 228;
+const SYSCALL_MUNLOCK: usize = 229;
+const SYSCALL_MINCORE: usize = 232;
+const SYSCALL_MADVISE: usize = 233;
 const SYSCALL_SET_PRIORITY: usize = 140;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETPPID: usize = 173;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_MEM_USAGE: usize = 411;
+const SYSCALL_FREE_FRAMES: usize = 412;
+const SYSCALL_MMAP_EX: usize = 413;
+const SYSCALL_PAUSE: usize = 414;
+const SYSCALL_NOTIFY: usize = 415;
+const SYSCALL_KERNEL_MEMINFO: usize = 416;
+const SYSCALL_CLOCK_GETRES: usize = 417;
+const SYSCALL_TASK_STATS: usize = 418;
+const SYSCALL_PIPE_OPEN: usize = 419;
+#[cfg(feature = "peek_task")]
+const SYSCALL_PEEK_TASK: usize = 420;
+const SYSCALL_SET_DEADLINE: usize = 421;
+const SYSCALL_LAST_MAP_ERROR: usize = 422;
+const SYSCALL_GET_APP_INFO: usize = 423;
+const SYSCALL_PREEMPT_DISABLE: usize = 424;
+const SYSCALL_PREEMPT_ENABLE: usize = 425;
 
+pub mod errno;
 mod fs;
 mod process;
 
+use crate::task::{count_syscall, TaskStats};
+use errno::ENOSYS;
 use fs::*;
 use process::*;
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     // LAB1: You may need to update syscall info here.
+    count_syscall(syscall_id);
     match syscall_id {
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut [usize; 2]),
+        SYSCALL_PIPE_OPEN => sys_pipe_open(args[0], args[1]),
+        SYSCALL_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_SLEEP => sys_sleep(args[0]),
+        SYSCALL_PREEMPT_DISABLE => sys_preempt_disable(),
+        SYSCALL_PREEMPT_ENABLE => sys_preempt_enable(),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
+        SYSCALL_CLOCK_GETRES => sys_clock_getres(args[0] as *mut TimeSpec),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MLOCK => sys_mlock(args[0], args[1]),
+        SYSCALL_MUNLOCK => sys_munlock(args[0], args[1]),
+        SYSCALL_MINCORE => sys_mincore(args[0], args[1], args[2] as *mut u8),
+        SYSCALL_MADVISE => sys_madvise(args[0], args[1], args[2]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
+        SYSCALL_SCHED_GETPARAM => sys_sched_getparam(args[0] as *mut SchedParam),
+        SYSCALL_SET_DEADLINE => sys_set_deadline(args[0]),
+        SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETPPID => sys_getppid(),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_GET_APP_INFO => sys_get_app_info(args[0] as *mut AppInfo),
+        SYSCALL_MEM_USAGE => sys_mem_usage(args[0] as *mut MemUsage),
+        SYSCALL_TASK_STATS => sys_task_stats(args[0] as *mut TaskStats),
+        SYSCALL_FREE_FRAMES => sys_free_frames(),
+        SYSCALL_LAST_MAP_ERROR => sys_last_map_error(),
+        SYSCALL_MMAP_EX => sys_mmap_ex(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_PAUSE => sys_pause(),
+        SYSCALL_NOTIFY => sys_notify(args[0]),
+        SYSCALL_KERNEL_MEMINFO => sys_kernel_meminfo(args[0] as *mut MemInfo),
+        #[cfg(feature = "peek_task")]
+        SYSCALL_PEEK_TASK => sys_peek_task(args[0], args[1], args[2] as *mut u8, args[3]),
+        _ => {
+            error!("[kernel] Unsupported syscall_id: {}", syscall_id);
+            ENOSYS
+        }
     }
 }
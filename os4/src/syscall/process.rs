@@ -1,12 +1,39 @@
 //! Process management syscalls
 
-use crate::config::MAX_SYSCALL_NUM;
-use crate::mm::{translate_by_token, VirtAddr};
+use super::errno::{errno_str, EINVAL};
+use crate::config::{HUGE_PAGE_SIZE, MAX_SYSCALL_NUM, SCHED_POLICY};
+use crate::mm::{frame_stats, free_frame_count, heap_stats, VirtAddr};
 use crate::task::{
-    current_user_token, exit_current_and_run_next, get_current_run_time, get_current_task_status,
-    get_syscall_times, suspend_current_and_run_next, task_mmap, task_munmap, TaskStatus,
+    block_current_and_run_next, copy_to_current_user, disable_current_preemption,
+    enable_current_preemption, exit_current_and_run_next, exit_group, get_current_app_info,
+    get_current_elapsed_time, get_current_map_conflict, get_current_mem_usage, get_current_parent,
+    get_current_sched_state, get_current_task_id, get_current_task_stats, get_current_task_status,
+    get_syscall_times, set_current_deadline, set_current_priority, sleep_current_and_run_next,
+    suspend_current_and_run_next, task_discard, task_mincore, task_mlock, task_mmap,
+    task_mmap_with, task_mprotect, task_munlock, task_munmap, wake_task, MmapBacking, TaskStats,
+    TaskStatus,
 };
-use crate::timer::get_time_us;
+use crate::timer::{get_resolution_ns, get_time_us, time_slice_left_us};
+
+/// `sys_mmap_ex` flag: round start and length to megapage boundaries
+const MMAP_HUGE_ALIGN: usize = 1 << 0;
+/// `sys_mmap_ex` flag: back the range with megapages, implies `MMAP_HUGE_ALIGN`
+const MMAP_HUGE: usize = 1 << 1;
+/// `sys_mmap_ex` flag: only reserve the range, fault pages in on first access
+const MMAP_LAZY: usize = 1 << 2;
+/// `sys_mmap_ex` flag: fill the new pages with the `fill` argument, eager maps only
+const MMAP_FILL: usize = 1 << 3;
+/// `sys_mmap_ex` flag: skip zeroing the new frames, eager maps only.
+///
+/// SECURITY: the pages show whatever was last in their frames, which can be
+/// another task's data or the kernel's. Only for tests and benchmarks that
+/// write every page before reading it, and only honored in a build with
+/// the `mmap_uninit` feature; otherwise it is refused with `EINVAL`.
+const MMAP_UNINITIALIZED: usize = 1 << 4;
+const MMAP_FLAGS: usize = MMAP_HUGE_ALIGN | MMAP_HUGE | MMAP_LAZY | MMAP_FILL | MMAP_UNINITIALIZED;
+
+/// `sys_madvise` advice: drop resident pages, refault them as zero pages
+const MADV_DONTNEED: usize = 4;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -15,6 +42,13 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
+#[repr(C)]
+#[derive(Debug)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -22,58 +56,363 @@ pub struct TaskInfo {
     pub time: usize,
 }
 
+/// Longest app name `AppInfo` holds, longer ones are cut short.
+pub const APP_NAME_LEN: usize = 32;
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct AppInfo {
+    /// the name from the loader's table, NUL padded
+    pub name: [u8; APP_NAME_LEN],
+    pub entry: usize,
+    /// lowest page of the loaded image
+    pub base: usize,
+    /// bytes from `base` to the end of the image's last page
+    pub size: usize,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct MemUsage {
+    pub resident_pages: usize,
+    pub peak_resident_pages: usize,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct MemInfo {
+    pub heap_used: usize,
+    pub heap_peak: usize,
+    pub frames_free: usize,
+    pub frames_total: usize,
+}
+
+/// scheduling state of a task; `policy` is a `SchedPolicy` as a number, in
+/// declaration order from `RoundRobin` = 0
+#[repr(C)]
+#[derive(Debug)]
+pub struct SchedParam {
+    pub priority: isize,
+    pub stride: usize,
+    /// time until the current time slice ends, in us
+    pub time_slice_left: usize,
+    pub policy: usize,
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     info!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
+/// End every task, not just the caller, and stop the run with `exit_code`;
+/// a non-zero code makes the emulator exit with a failure.
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    exit_group(exit_code)
+}
+
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     suspend_current_and_run_next();
     0
 }
 
+/// Hold off timer preemption until `sys_preempt_enable`, for a short
+/// critical section; see `MAX_PREEMPT_DISABLE_TICKS`.
+pub fn sys_preempt_disable() -> isize {
+    disable_current_preemption();
+    0
+}
+
+/// Allow timer preemption again. A preemption held off meanwhile happens
+/// now; returns how many ticks were held off.
+pub fn sys_preempt_enable() -> isize {
+    enable_current_preemption() as isize
+}
+
+/// Sleep for `ms` milliseconds without taking the CPU.
+pub fn sys_sleep(ms: usize) -> isize {
+    sleep_current_and_run_next(ms);
+    0
+}
+
+/// Block until another task calls `sys_notify` on this one.
+pub fn sys_pause() -> isize {
+    block_current_and_run_next();
+    0
+}
+
+pub fn sys_getpid() -> isize {
+    get_current_task_id() as isize
+}
+
+/// Parent of the current task, -1 when it has none as for every app loaded at boot.
+pub fn sys_getppid() -> isize {
+    get_current_parent().map_or(-1, |parent| parent as isize)
+}
+
+/// Wake task `pid` out of `sys_pause`; fails with no effect if it is not paused.
+pub fn sys_notify(pid: usize) -> isize {
+    wake_task(pid)
+}
+
 // YOUR JOB: 引入虚地址后重写 sys_get_time
 pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     let us = get_time_us();
-    let ts_pa = translate_by_token(current_user_token(), VirtAddr::from(_ts as usize))
-        .unwrap()
-        .0 as *mut TimeVal;
-    unsafe {
-        *ts_pa = TimeVal {
-            sec: us / 1_000_000,
-            usec: us % 1_000_000,
-        };
+    let time_val = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    copy_out(_ts, &time_val)
+}
+
+/// Report the granularity of the timer behind the time syscalls.
+pub fn sys_clock_getres(res: *mut TimeSpec) -> isize {
+    let ns = get_resolution_ns();
+    let time_spec = TimeSpec {
+        tv_sec: ns / 1_000_000_000,
+        tv_nsec: ns % 1_000_000_000,
+    };
+    copy_out(res, &time_spec)
+}
+
+// CLUE: 从 ch4 开始不再对调度算法进行测试~
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
     }
+    set_current_priority(prio);
+    prio
+}
+
+/// Ask to be done within `ms` from now, for `SchedPolicy::Edf`; 0 clears the
+/// deadline. Missing it is only logged.
+pub fn sys_set_deadline(ms: usize) -> isize {
+    set_current_deadline(ms);
     0
 }
 
-// CLUE: 从 ch4 开始不再对调度算法进行测试~
-pub fn sys_set_priority(_prio: isize) -> isize {
-    -1
+/// Describe the current app: its name, entry point and loaded image.
+pub fn sys_get_app_info(info: *mut AppInfo) -> isize {
+    let (name, entry, image) = get_current_app_info();
+    let mut app_info = AppInfo {
+        name: [0; APP_NAME_LEN],
+        entry,
+        base: image.start,
+        size: image.end - image.start,
+    };
+    let len = name.len().min(APP_NAME_LEN);
+    app_info.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+    copy_out(info, &app_info)
+}
+
+/// Report the scheduling state of the current task.
+pub fn sys_sched_getparam(param: *mut SchedParam) -> isize {
+    let (priority, stride) = get_current_sched_state();
+    let sched_param = SchedParam {
+        priority,
+        stride,
+        time_slice_left: time_slice_left_us(),
+        policy: SCHED_POLICY as usize,
+    };
+    copy_out(param, &sched_param)
 }
 
 // YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
 pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
-    task_mmap(_start, _len, _port)
+    match task_mmap(_start, _len, _port) {
+        0 => 0,
+        err => {
+            debug!("[kernel] sys_mmap failed: {}", errno_str(err));
+            -1
+        }
+    }
+}
+
+/// Where the last mmap of a valid range ran into memory already mapped: the
+/// address of the first page in the way, or `EINVAL` if it did not fail
+/// that way. `sys_mmap` itself keeps returning a bare -1.
+pub fn sys_last_map_error() -> isize {
+    match get_current_map_conflict() {
+        Some(vpn) => VirtAddr::from(vpn).0 as isize,
+        None => EINVAL,
+    }
+}
+
+/// Like `sys_mmap`, but takes `flags` (and `fill` for `MMAP_FILL`) and
+/// returns the start actually mapped or a negative errno.
+pub fn sys_mmap_ex(start: usize, len: usize, port: usize, flags: usize, fill: usize) -> isize {
+    if flags & !MMAP_FLAGS != 0 {
+        return EINVAL;
+    }
+    let backing = match (
+        flags & MMAP_HUGE != 0,
+        flags & MMAP_LAZY != 0,
+        flags & MMAP_FILL != 0,
+        flags & MMAP_UNINITIALIZED != 0,
+    ) {
+        (false, false, false, false) => MmapBacking::Framed,
+        (false, false, true, false) if fill <= u8::MAX as usize => MmapBacking::Filled(fill as u8),
+        (false, true, false, false) => MmapBacking::Lazy,
+        (true, false, false, false) => MmapBacking::Huge,
+        (false, false, false, true) if cfg!(feature = "mmap_uninit") => MmapBacking::Uninit,
+        _ => return EINVAL,
+    };
+    let (start, len) = if flags & (MMAP_HUGE_ALIGN | MMAP_HUGE) != 0 {
+        match (
+            align_up(start, HUGE_PAGE_SIZE),
+            align_up(len, HUGE_PAGE_SIZE),
+        ) {
+            (Some(start), Some(len)) => (start, len),
+            // rounding up would wrap to a small address
+            _ => return EINVAL,
+        }
+    } else {
+        (start, len)
+    };
+    match task_mmap_with(start, len, port, backing) {
+        0 => start as isize,
+        err => err,
+    }
+}
+
+/// `value` rounded up to a multiple of `align`, a power of two; `None` if
+/// that does not fit in a `usize`.
+fn align_up(value: usize, align: usize) -> Option<usize> {
+    value
+        .checked_add(align - 1)
+        .map(|value| value & !(align - 1))
 }
 
 pub fn sys_munmap(_start: usize, _len: usize) -> isize {
-    task_munmap(_start, _len)
+    match task_munmap(_start, _len) {
+        0 => 0,
+        err => {
+            debug!("[kernel] sys_munmap failed: {}", errno_str(err));
+            -1
+        }
+    }
+}
+
+/// Change the permission of `[start, start + len)` to `port`, bits as in `sys_mmap`.
+pub fn sys_mprotect(start: usize, len: usize, port: usize) -> isize {
+    task_mprotect(start, len, port)
+}
+
+/// Pin `[start, start + len)` so a future eviction scanner leaves it resident.
+pub fn sys_mlock(start: usize, len: usize) -> isize {
+    task_mlock(start, len)
+}
+
+pub fn sys_munlock(start: usize, len: usize) -> isize {
+    task_munlock(start, len)
+}
+
+/// Write one byte per page of `[start, start + len)` to `vec`: 1 if the page
+/// is resident, 0 if it is only reserved. -1 if the range is not all mapped
+/// or reserved, or `vec` cannot be written.
+pub fn sys_mincore(start: usize, len: usize, vec: *mut u8) -> isize {
+    let result = match task_mincore(start, len) {
+        Ok(residency) => copy_to_current_user(vec as usize, &residency).min(0),
+        Err(err) => err,
+    };
+    match result {
+        0 => 0,
+        err => {
+            debug!("[kernel] sys_mincore failed: {}", errno_str(err));
+            -1
+        }
+    }
+}
+
+/// Give advice about `[start, start + len)`; only `MADV_DONTNEED` is known.
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    match advice {
+        MADV_DONTNEED => task_discard(start, len),
+        _ => EINVAL,
+    }
 }
 
 // YOUR JOB: 引入虚地址后重写 sys_task_info
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
-    let ti_pa = translate_by_token(current_user_token(), VirtAddr::from(ti as usize))
-        .unwrap()
-        .0 as *mut TaskInfo;
-    unsafe {
-        *ti_pa = TaskInfo {
-            status: get_current_task_status(),
-            syscall_times: get_syscall_times(),
-            time: get_current_run_time(),
+    let task_info = TaskInfo {
+        status: get_current_task_status(),
+        syscall_times: get_syscall_times(),
+        // the lab counts wall time from the first run, not only time
+        // spent `Running` as `sys_task_stats` does
+        time: get_current_elapsed_time(),
+    };
+    copy_out(ti, &task_info)
+}
+
+/// Report every counter of the current task at once; `sys_task_info` and
+/// `sys_mem_usage` return subsets of it.
+pub fn sys_task_stats(ts: *mut TaskStats) -> isize {
+    copy_out(ts, &get_current_task_stats())
+}
+
+/// report the current task's resident and peak resident page counts
+pub fn sys_mem_usage(mu: *mut MemUsage) -> isize {
+    let (resident_pages, peak_resident_pages) = get_current_mem_usage();
+    let mem_usage = MemUsage {
+        resident_pages,
+        peak_resident_pages,
+    };
+    copy_out(mu, &mem_usage)
+}
+
+/// Write `value` to the user pointer `dst`, returning 0 or `EFAULT`.
+fn copy_out<T>(dst: *mut T, value: &T) -> isize {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    match copy_to_current_user(dst as usize, bytes) {
+        err if err < 0 => err,
+        _ => 0,
+    }
+}
+
+/// Copy `len` bytes at `addr` in task `pid` to `buf`, a page at a time.
+/// Every byte has to be resident in `pid`, which is not faulted in.
+#[cfg(feature = "peek_task")]
+pub fn sys_peek_task(pid: usize, addr: usize, buf: *mut u8, len: usize) -> isize {
+    use super::errno::EFAULT;
+    use crate::config::PAGE_SIZE;
+    use crate::task::peek_task;
+    let mut chunk = alloc::vec![0u8; PAGE_SIZE];
+    let mut copied = 0;
+    while copied < len {
+        let count = PAGE_SIZE.min(len - copied);
+        let src_va = match addr.checked_add(copied) {
+            Some(src_va) => src_va,
+            None => return EFAULT,
+        };
+        match peek_task(pid, &mut chunk[..count], src_va) {
+            err if err < 0 => return err,
+            _ => {}
+        }
+        match copy_to_current_user(buf as usize + copied, &chunk[..count]) {
+            err if err < 0 => return err,
+            _ => copied += count,
         }
     }
-    0
+    len as isize
+}
+
+/// report kernel heap usage and frame allocator occupancy
+pub fn sys_kernel_meminfo(mi: *mut MemInfo) -> isize {
+    let (heap_used, heap_peak) = heap_stats();
+    let (frames_free, frames_total) = frame_stats();
+    let mem_info = MemInfo {
+        heap_used,
+        heap_peak,
+        frames_free,
+        frames_total,
+    };
+    copy_out(mi, &mem_info)
+}
+
+/// number of physical frames still free, saturated to `isize::MAX`
+pub fn sys_free_frames() -> isize {
+    free_frame_count().min(isize::MAX as usize) as isize
 }
@@ -1,7 +1,10 @@
 //! Implementation of [`TaskContext`]
+use super::idle::{idle_loop, idle_stack_top};
+use super::switch::__switch;
 use crate::trap::trap_return;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 #[repr(C)]
 /// task context structure containing some registers
 pub struct TaskContext {
@@ -11,7 +14,11 @@ pub struct TaskContext {
 }
 
 impl TaskContext {
-    pub fn zero_init() -> Self {
+    /// A context to switch away from and never back, like the one
+    /// `run_first_task` starts from. `__switch` only stores into the context
+    /// it leaves, so the zeros are overwritten before anything reads them;
+    /// restoring one that was never switched away from would jump to address 0.
+    pub const fn zero_init() -> Self {
         Self {
             ra: 0,
             sp: 0,
@@ -25,4 +32,55 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    /// Whether `__switch` may load this context: it has somewhere to return
+    /// to and a stack to do it on, so it is not a bare `zero_init`.
+    pub fn is_restorable(&self) -> bool {
+        self.ra != 0 && self.sp != 0
+    }
+    /// context that enters the idle loop on its own stack
+    pub fn goto_idle() -> Self {
+        Self {
+            ra: idle_loop as usize,
+            sp: idle_stack_top(),
+            s: [0; 12],
+        }
+    }
+}
+
+const SWITCH_TEST_STACK_SIZE: usize = 4096 * 2;
+static mut SWITCH_TEST_STACK: [u8; SWITCH_TEST_STACK_SIZE] = [0; SWITCH_TEST_STACK_SIZE];
+/// where `zero_init_switch_test` is switched away from and back to
+static mut SWITCH_TEST_RETURN_CX: TaskContext = TaskContext::zero_init();
+static SWITCH_TEST_REACHED: AtomicBool = AtomicBool::new(false);
+
+/// Stands in for a real task: note it ran, then switch back to the test from
+/// another write-only context.
+fn switch_test_entry() -> ! {
+    SWITCH_TEST_REACHED.store(true, Ordering::SeqCst);
+    let mut unused = TaskContext::zero_init();
+    unsafe {
+        __switch(&mut unused as *mut _, &SWITCH_TEST_RETURN_CX as *const _);
+    }
+    unreachable!("switched back into a write-only context");
+}
+
+#[allow(unused)]
+/// switching away from a `zero_init` context reaches the next one, and
+/// leaves the zeros overwritten with somewhere to come back to
+pub fn zero_init_switch_test() {
+    SWITCH_TEST_REACHED.store(false, Ordering::SeqCst);
+    unsafe {
+        SWITCH_TEST_RETURN_CX = TaskContext::zero_init();
+        assert!(!SWITCH_TEST_RETURN_CX.is_restorable());
+        let next = TaskContext {
+            ra: switch_test_entry as usize,
+            sp: SWITCH_TEST_STACK.as_ptr() as usize + SWITCH_TEST_STACK_SIZE,
+            s: [0; 12],
+        };
+        assert!(next.is_restorable());
+        __switch(&mut SWITCH_TEST_RETURN_CX as *mut _, &next as *const _);
+        assert!(SWITCH_TEST_RETURN_CX.is_restorable());
+    }
+    assert!(SWITCH_TEST_REACHED.load(Ordering::SeqCst));
+    info!("zero_init_switch_test passed!");
 }
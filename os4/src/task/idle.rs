@@ -0,0 +1,31 @@
+//! The idle task.
+//!
+//! It is not part of the task list and never counts as an app. When every
+//! remaining task sleeps, `run_next_task` switches to it, and it waits with
+//! `wfi` until a sleeper can run again.
+
+use crate::timer::set_next_trigger;
+use riscv::register::sip;
+
+const IDLE_STACK_SIZE: usize = 4096 * 2;
+
+/// kernel stack the idle loop runs on
+static mut IDLE_STACK: [u8; IDLE_STACK_SIZE] = [0; IDLE_STACK_SIZE];
+
+pub fn idle_stack_top() -> usize {
+    unsafe { IDLE_STACK.as_ptr() as usize + IDLE_STACK_SIZE }
+}
+
+/// Entry of the idle context, see `TaskContext::goto_idle`.
+pub fn idle_loop() -> ! {
+    loop {
+        super::run_from_idle();
+        // interrupts are off in the kernel, but a pending timer still ends `wfi`
+        unsafe {
+            riscv::asm::wfi();
+        }
+        if sip::read().stimer() {
+            set_next_trigger();
+        }
+    }
+}
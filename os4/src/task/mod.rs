@@ -10,20 +10,34 @@
 //! might not be what you expect.
 
 mod context;
+mod idle;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::config::{CLOCK_FREQ, MAX_SYSCALL_NUM};
-use crate::loader::{get_app_data, get_num_app};
-use crate::mm::{MapPermission, VPNRange, VirtAddr};
+use crate::config::{
+    SchedPolicy, APP_TIME_BUDGET_MS, BIG_STRIDE, BOOT_ORDER, DEADLOCK_EXIT_CODE, FIRST_TASK_ID,
+    MAX_PREEMPT_DISABLE_TICKS, MAX_SYSCALL_NUM, MAX_TASKS, MUNMAP_PARTIAL_END,
+    PREEMPT_WARN_THRESHOLD, PRIORITY_AGING_CAP, PROFILE_SAMPLE_TICKS, SCHED_POLICY,
+    SCHED_WATCHDOG_TICKS, TRAP_CONTEXT,
+};
+use crate::fs::{alloc_fd, FileDescriptor};
+use crate::loader::{get_app_data, get_app_name, get_num_app};
+use crate::mm::{
+    free_frame_count, table_frames_for, user_range, MapPermission, VPNRange, VirtAddr, VirtPageNum,
+};
+use crate::sbi::{shutdown, shutdown_failure};
 use crate::sync::UPSafeCell;
-use crate::timer::{get_time, get_time_ms};
+use crate::syscall::errno::{EEXIST, EINVAL, EMFILE, ENOMEM, ESRCH};
+use crate::timer::get_time_ms;
 use crate::trap::TrapContext;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
+use core::ops::Range;
 use lazy_static::*;
 pub use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+use task::{kernel_stack_intact, write_kernel_stack_canary};
+pub use task::{TaskControlBlock, TaskStats, TaskStatus};
 
 pub use context::TaskContext;
 
@@ -49,24 +63,149 @@ struct TaskManagerInner {
     tasks: Vec<TaskControlBlock>,
     /// id of current `Running` task
     current_task: usize,
+    /// what is left of [`BOOT_ORDER`], front first
+    boot_order: VecDeque<usize>,
+    /// where the idle loop resumes when no task is ready
+    idle_task_cx: TaskContext,
+    /// timer ticks since the last switch to a different task while another
+    /// was `Ready`, see [`SCHED_WATCHDOG_TICKS`]
+    stalled_ticks: usize,
+    /// timer ticks taken while a task ran, see [`PROFILE_SAMPLE_TICKS`]
+    profile_ticks: usize,
+    /// samples that found each task running, by id
+    cpu_profile: [u64; MAX_TASKS],
+}
+
+impl TaskManagerInner {
+    /// The TCB of the current task, panicking with the bad index if
+    /// `current_task` is out of range.
+    fn current_tcb(&self) -> &TaskControlBlock {
+        let (current, num_tasks) = (self.current_task, self.tasks.len());
+        self.tasks.get(current).unwrap_or_else(|| {
+            panic!(
+                "current_task {} out of range, only {} tasks",
+                current, num_tasks
+            )
+        })
+    }
+    fn current_tcb_mut(&mut self) -> &mut TaskControlBlock {
+        let (current, num_tasks) = (self.current_task, self.tasks.len());
+        self.tasks.get_mut(current).unwrap_or_else(|| {
+            panic!(
+                "current_task {} out of range, only {} tasks",
+                current, num_tasks
+            )
+        })
+    }
+    /// Account the slice of the task switched out at `now` and set `next`
+    /// up as the `Running` one.
+    fn switch_in(&mut self, next: usize, now: usize) {
+        let next_task = &mut self.tasks[next];
+        debug_assert!(next_task.task_cx.is_restorable());
+        next_task.last_scheduled = now;
+        next_task.task_status = TaskStatus::Running;
+        next_task.stride += BIG_STRIDE / next_task.priority as usize;
+        next_task.stats.schedules += 1;
+        if next_task.start_time.is_none() {
+            next_task.start_time = Some(now);
+        }
+        if let Some(late) = next_task.take_missed_deadline(now) {
+            warn!("[kernel] app_{} missed its deadline by {}ms", next, late);
+        }
+        if next != self.current_task {
+            self.stalled_ticks = 0;
+        }
+        self.current_task = next;
+    }
+    /// The `Ready` task other than `exclude` that `policy` picks. Candidates
+    /// are scanned in task list order starting after the current task.
+    fn pick_by_policy(&mut self, policy: SchedPolicy, exclude: Option<usize>) -> Option<usize> {
+        let (current, num_tasks) = (self.current_task, self.tasks.len());
+        let ready: Vec<usize> = (current + 1..current + num_tasks + 1)
+            .map(|id| id % num_tasks)
+            .filter(|id| Some(*id) != exclude && self.tasks[*id].task_status.is_runnable())
+            .collect();
+        match policy {
+            SchedPolicy::RoundRobin => ready.first().copied(),
+            SchedPolicy::Priority => {
+                let mut aged: Vec<(isize, isize)> = self
+                    .tasks
+                    .iter()
+                    .map(|task| (task.priority, task.priority_boost))
+                    .collect();
+                let next = pick_aged(&ready, &mut aged);
+                for (task, (_, boost)) in self.tasks.iter_mut().zip(aged) {
+                    task.priority_boost = boost;
+                }
+                next
+            }
+            SchedPolicy::Stride => ready
+                .iter()
+                .copied()
+                .min_by_key(|id| self.tasks[*id].stride),
+            SchedPolicy::Edf => {
+                let deadlines: Vec<Option<usize>> = self
+                    .tasks
+                    .iter()
+                    .map(|task| task.absolute_deadline)
+                    .collect();
+                pick_earliest_deadline(&ready, &deadlines)
+            }
+        }
+    }
+    /// Count a timer tick, charging a sample to the running task every
+    /// `PROFILE_SAMPLE_TICKS` ticks.
+    fn sample_cpu_profile(&mut self) {
+        self.profile_ticks += 1;
+        if self.profile_ticks % PROFILE_SAMPLE_TICKS != 0 {
+            return;
+        }
+        if let Some(samples) = self.cpu_profile.get_mut(self.current_task) {
+            *samples += 1;
+        }
+    }
 }
 
 lazy_static! {
     /// a `TaskManager` instance through lazy_static!
     pub static ref TASK_MANAGER: TaskManager = {
         info!("init TASK_MANAGER");
-        let num_app = get_num_app();
-        info!("num_app = {}", num_app);
+        info!("num_app = {}", get_num_app());
         let mut tasks: Vec<TaskControlBlock> = Vec::new();
-        for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(get_app_data(i), i));
+        for i in 0..get_num_app() {
+            // ids stay dense so that a task's id is its index in the list
+            match TaskControlBlock::new(get_app_name(i), get_app_data(i), tasks.len()) {
+                Ok(task) => tasks.push(task),
+                Err(reason) => error!("[kernel] Skip app_{}: {}", i, reason),
+            }
+        }
+        let num_app = tasks.len();
+        assert!(num_app > 0, "no loadable app!");
+        assert!(
+            FIRST_TASK_ID < num_app,
+            "FIRST_TASK_ID {} out of range, only {} apps loaded",
+            FIRST_TASK_ID,
+            num_app
+        );
+        for &id in BOOT_ORDER {
+            assert!(
+                id < num_app,
+                "BOOT_ORDER names app {}, only {} apps loaded",
+                id,
+                num_app
+            );
         }
         TaskManager {
             num_app,
             inner: unsafe {
                 UPSafeCell::new(TaskManagerInner {
                     tasks,
-                    current_task: 0,
+                    current_task: FIRST_TASK_ID,
+                    boot_order: BOOT_ORDER.iter().copied().collect(),
+                    idle_task_cx: TaskContext::goto_idle(),
+                    stalled_ticks: 0,
+                    profile_ticks: 0,
+                    cpu_profile: [0; MAX_TASKS],
                 })
             },
         }
@@ -80,11 +219,19 @@ impl TaskManager {
     /// But in ch4, we load apps statically, so the first task is a real app.
     fn run_first_task(&self) -> ! {
         let mut inner = self.inner.exclusive_access();
-        let next_task = &mut inner.tasks[0];
+        inner.current_task = FIRST_TASK_ID;
+        info!("[kernel] first task: app_{}", FIRST_TASK_ID);
+        let next_task = &mut inner.tasks[FIRST_TASK_ID];
         next_task.task_status = TaskStatus::Running;
+        next_task.stride += BIG_STRIDE / next_task.priority as usize;
+        next_task.stats.schedules += 1;
+        debug_assert!(next_task.task_cx.is_restorable());
         let next_task_cx_ptr = &next_task.task_cx as *const TaskContext;
-        next_task.start_time = Some(get_time());
+        let now = get_time_ms();
+        next_task.start_time = Some(now);
+        next_task.last_scheduled = now;
         drop(inner);
+        // write-only: nothing ever switches back to the boot stack
         let mut _unused = TaskContext::zero_init();
         // before this, we should drop local variables that must be dropped manually
         unsafe {
@@ -96,136 +243,716 @@ impl TaskManager {
     /// Change the status of current `Running` task into `Ready`.
     fn mark_current_suspended(&self) {
         let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+        inner.current_tcb_mut().task_status = TaskStatus::Ready;
+    }
+
+    /// Put the current `Running` task to sleep for `ms` milliseconds.
+    fn mark_current_sleeping(&self, ms: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_tcb_mut();
+        task.wake_at = get_time_ms() + ms;
+        task.task_status = TaskStatus::Sleeping;
+    }
+
+    /// Change the status of current `Running` task into `Blocked`.
+    fn mark_current_blocked(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_tcb_mut().task_status = TaskStatus::Blocked;
     }
 
-    /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
+    /// Make the `Blocked` task `task_id` ready again, returning 0 or an errno.
+    /// A wakeup for a task that is not blocked is dropped, not remembered.
+    fn wake_task(&self, task_id: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        match inner.tasks.get_mut(task_id) {
+            None => ESRCH,
+            Some(task) if task.task_status.is_terminated() => ESRCH,
+            Some(task) if task.task_status != TaskStatus::Blocked => EINVAL,
+            Some(task) => {
+                task.task_status = TaskStatus::Ready;
+                0
+            }
+        }
+    }
+
+    /// Change the status of current `Running` task into `Exited` with `exit_code`.
+    fn mark_current_exited(&self, exit_code: i32) {
         let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+        let task = inner.current_tcb_mut();
+        task.task_status = TaskStatus::Exited;
+        task.exit_code = Some(exit_code);
+        if let Some(late) = task.take_missed_deadline(get_time_ms()) {
+            warn!(
+                "[kernel] app_{} exited {}ms past its deadline",
+                current, late
+            );
+        }
+        task.release_resources();
+        #[cfg(debug_assertions)]
+        task.memory_set.report_unreleased(current);
+    }
+
+    /// End every task with the current one, then stop the run with
+    /// `exit_code`.
+    fn exit_group(&self, exit_code: i32) -> ! {
+        let mut inner = self.inner.exclusive_access();
+        info!(
+            "[kernel] app_{} ended the run with exit_group({})",
+            inner.current_task, exit_code
+        );
+        for (app_id, task) in inner.tasks.iter_mut().enumerate() {
+            if task.task_status.is_terminated() {
+                continue;
+            }
+            if task.task_status != TaskStatus::Running {
+                info!(
+                    "[kernel] app_{}: {}, ended by exit_group",
+                    app_id, task.task_status
+                );
+            }
+            task.task_status = TaskStatus::Exited;
+            task.exit_code = Some(exit_code);
+            task.release_resources();
+            #[cfg(debug_assertions)]
+            task.memory_set.report_unreleased(app_id);
+        }
+        drop(inner);
+        self.finish(exit_code)
     }
 
     /// Find next task to run and return task id.
     ///
-    /// In this case, we only return the first `Ready` task in task list.
+    /// Candidates are the `Ready` tasks, scanned in task list order starting
+    /// after the current one; [`SCHED_POLICY`] decides which of them wins.
+    /// Until [`BOOT_ORDER`] is used up its next `Ready` entry wins instead.
     fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+        let mut inner = self.inner.exclusive_access();
+        let now = get_time_ms();
+        for task in inner.tasks.iter_mut() {
+            if task.task_status == TaskStatus::Sleeping && task.wake_at <= now {
+                task.task_status = TaskStatus::Ready;
+            }
+        }
+        while let Some(id) = inner.boot_order.pop_front() {
+            if inner.tasks[id].task_status.is_runnable() {
+                return Some(id);
+            }
+        }
+        inner.pick_by_policy(SCHED_POLICY, None)
     }
 
     /// Get the current 'Running' task's token.
     fn get_current_token(&self) -> usize {
         let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_user_token()
+        inner.current_tcb().get_user_token()
     }
 
     #[allow(clippy::mut_from_ref)]
     /// Get the current 'Running' task's trap contexts.
     fn get_current_trap_cx(&self) -> &mut TrapContext {
         let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_trap_cx()
+        inner.current_tcb().get_trap_cx()
     }
 
     /// Switch current `Running` task to the task we have found,
     /// or there is no `Ready` task and we can exit with all applications completed
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
+        self.run_task(self.find_next_task());
+    }
+
+    /// Switch to `next`, or wait for a sleeper or finish if there is none.
+    fn run_task(&self, next: Option<usize>) {
+        if let Some(next) = next {
             let mut inner = self.inner.exclusive_access();
             let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
+            let now = get_time_ms();
+            let task = inner.current_tcb_mut();
+            task.stats.run_time += now - task.last_scheduled;
+            inner.switch_in(next, now);
+            if next == current {
+                // the only ready task, keep running it rather than
+                // switching from a context to itself
+                return;
+            }
             let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
             let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            if inner.tasks[next].start_time.is_none() {
-                inner.tasks[next].start_time = Some(get_time());
-            }
             drop(inner);
             // before this, we should drop local variables that must be dropped manually
             unsafe {
                 __switch(current_task_cx_ptr, next_task_cx_ptr);
             }
             // go back to user mode
+        } else if self.any_sleeping() {
+            // nothing to run until a sleeper wakes up, wait in the idle loop
+            let mut inner = self.inner.exclusive_access();
+            let task = inner.current_tcb_mut();
+            task.stats.run_time += get_time_ms() - task.last_scheduled;
+            let current_task_cx_ptr = &mut task.task_cx as *mut TaskContext;
+            let idle_task_cx_ptr = &inner.idle_task_cx as *const TaskContext;
+            drop(inner);
+            unsafe {
+                __switch(current_task_cx_ptr, idle_task_cx_ptr);
+            }
         } else {
-            panic!("All applications completed!");
+            self.finish_idle();
         }
     }
 
+    /// Called by the idle loop: switch to a task if one is ready, return if
+    /// every remaining task is still asleep.
+    fn run_from_idle(&self) {
+        if let Some(next) = self.find_next_task() {
+            let mut inner = self.inner.exclusive_access();
+            inner.switch_in(next, get_time_ms());
+            let idle_task_cx_ptr = &mut inner.idle_task_cx as *mut TaskContext;
+            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+            drop(inner);
+            unsafe {
+                __switch(idle_task_cx_ptr, next_task_cx_ptr);
+            }
+        } else if !self.any_sleeping() {
+            self.finish_idle();
+        }
+    }
+
+    fn any_sleeping(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .any(|task| task.task_status == TaskStatus::Sleeping)
+    }
+
+    /// Nothing is ready or asleep; finish, as a failure if some task is
+    /// still blocked, since nothing is left to notify it.
+    fn finish_idle(&self) -> ! {
+        let blocked = self
+            .inner
+            .exclusive_access()
+            .tasks
+            .iter()
+            .filter(|task| task.task_status == TaskStatus::Blocked)
+            .count();
+        if blocked > 0 {
+            // printed even with logging off, like the exit code
+            println!(
+                "[kernel] deadlock: {} apps blocked and none left to wake them",
+                blocked
+            );
+            self.finish(DEADLOCK_EXIT_CODE);
+        }
+        self.finish(0)
+    }
+
+    /// No task can run any more; report every app and stop, as a failure
+    /// for the emulator if `exit_code` is not 0. An SRST reset reason only
+    /// tells the SBI whether there was a failure, so the emulator exits with
+    /// its own failure status; the code itself is printed before that.
+    fn finish(&self, exit_code: i32) -> ! {
+        let inner = self.inner.exclusive_access();
+        for (app_id, task) in inner.tasks.iter().enumerate() {
+            match task.task_status {
+                TaskStatus::Exited => info!(
+                    "[kernel] app_{}: {}, exit code {}",
+                    app_id,
+                    task.task_status,
+                    task.exit_code.unwrap()
+                ),
+                // stuck, most likely in its last syscall
+                status => info!(
+                    "[kernel] app_{}: {}, last syscall {:?}",
+                    app_id,
+                    status,
+                    task.stats.last_syscall()
+                ),
+            }
+        }
+        drop(inner);
+        let profile = self.cpu_profile();
+        let total: u64 = profile.iter().sum();
+        if total > 0 {
+            for (app_id, samples) in profile.iter().enumerate().take(self.num_app) {
+                info!(
+                    "[kernel] app_{}: {} of {} cpu samples ({}%)",
+                    app_id,
+                    samples,
+                    total,
+                    samples * 100 / total
+                );
+            }
+        }
+        println!("[kernel] All applications completed!");
+        if exit_code != 0 {
+            println!("[kernel] exit code {}", exit_code);
+        }
+        if exit_code == 0 {
+            shutdown()
+        } else {
+            shutdown_failure()
+        }
+    }
+
+    fn sample_cpu_profile(&self) {
+        self.inner.exclusive_access().sample_cpu_profile();
+    }
+
+    fn cpu_profile(&self) -> [u64; MAX_TASKS] {
+        self.inner.exclusive_access().cpu_profile
+    }
+
     // 系统调用累加器
     fn count_syscall(&self, syscall_id: usize) {
+        let mut inner = TASK_MANAGER.inner.exclusive_access();
+        let stats = &mut inner.current_tcb_mut().stats;
+        stats.last_syscall = syscall_id;
         if syscall_id < MAX_SYSCALL_NUM {
-            let mut inner = TASK_MANAGER.inner.exclusive_access();
-            let current_task = inner.current_task;
-            inner.tasks[current_task].syscall_times[syscall_id] += 1;
+            // stuck at u32::MAX rather than wrapping to a small count
+            stats.syscall_times[syscall_id] = stats.syscall_times[syscall_id].saturating_add(1);
         }
     }
 
+    fn get_current_last_syscall(&self) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().stats.last_syscall()
+    }
+
     // 获取系统调用次数
     fn get_syscall_times(&self) -> [u32; MAX_SYSCALL_NUM] {
         let inner = TASK_MANAGER.inner.exclusive_access();
-        *inner.tasks[inner.current_task].syscall_times
+        inner.current_tcb().stats.syscall_times
+    }
+
+    /// Snapshot of every counter of the current task.
+    fn get_current_task_stats(&self) -> TaskStats {
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().stats_snapshot(get_time_ms())
+    }
+
+    fn get_current_task_id(&self) -> usize {
+        self.inner.exclusive_access().current_task
+    }
+
+    fn get_current_parent(&self) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().parent
     }
 
     fn get_current_task_status(&self) -> TaskStatus {
         let inner = self.inner.exclusive_access();
-        return inner.tasks[inner.current_task].task_status;
+        return inner.current_tcb().task_status;
     }
 
+    /// Time the current task has spent `Running`, in ms, leaving out the
+    /// slices other tasks ran in between.
     fn get_current_run_time(&self) -> usize {
-        let inner = TASK_MANAGER.inner.exclusive_access();
-        get_time_ms() - inner.tasks[inner.current_task].start_time.unwrap()
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().total_run_time(get_time_ms())
     }
 
-    fn task_mmap(&self, start: usize, len: usize, port: usize) -> isize {
-        let start_va = VirtAddr::from(start);
-        let end_va = VirtAddr::from(start + len);
-        if !start_va.aligned() || (port & !0x7) != 0 || (port & 0x7) == 0 {
-            return -1;
+    /// Wall time since the current task first ran, in ms.
+    fn get_current_elapsed_time(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        get_time_ms() - inner.current_tcb().start_time.unwrap()
+    }
+
+    /// Whether the current 'Running' task has used up [`APP_TIME_BUDGET_MS`].
+    fn current_exceeds_time_budget(&self) -> bool {
+        APP_TIME_BUDGET_MS.map_or(false, |budget| self.get_current_run_time() > budget)
+    }
+
+    /// Count a timer tick for the scheduler watchdog. Returns a task to
+    /// switch to by force once another task has been `Ready` for more than
+    /// `SCHED_WATCHDOG_TICKS` ticks without any switch. The task switched
+    /// to is the one `SCHED_POLICY` picks among the others.
+    fn watchdog_tick(&self) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        if next_other_ready(&inner.tasks, current).is_none() {
+            inner.stalled_ticks = 0;
+            return None;
+        }
+        inner.stalled_ticks += 1;
+        if inner.stalled_ticks <= SCHED_WATCHDOG_TICKS {
+            return None;
         }
+        let stalled_ticks = inner.stalled_ticks;
+        inner.stalled_ticks = 0;
+        let next = inner.pick_by_policy(SCHED_POLICY, Some(current))?;
+        warn!(
+            "[kernel] scheduler stall detected: app_{} kept the CPU for {} ticks while app_{} was ready, forcing a switch",
+            current, stalled_ticks, next
+        );
+        Some(next)
+    }
+
+    /// Hold off a timer preemption of the current task if it disabled them,
+    /// returning whether it did. Past `MAX_PREEMPT_DISABLE_TICKS` the task
+    /// is preempted anyway, with preemption enabled again.
+    fn defer_current_preemption(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = inner.current_tcb_mut();
+        let ticks = match task.deferred_ticks.as_mut() {
+            Some(ticks) => ticks,
+            None => return false,
+        };
+        *ticks += 1;
+        if *ticks <= MAX_PREEMPT_DISABLE_TICKS {
+            return true;
+        }
+        warn!(
+            "[kernel] app_{} kept preemption disabled for {} ticks, preempting it",
+            current, MAX_PREEMPT_DISABLE_TICKS
+        );
+        task.deferred_ticks = None;
+        false
+    }
+
+    /// Stop or resume timer preemption of the current task. Resuming
+    /// returns the ticks held off meanwhile, a preemption is due if above 0.
+    fn set_current_preemptible(&self, preemptible: bool) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_tcb_mut();
+        let deferred = task.deferred_ticks.unwrap_or(0);
+        task.deferred_ticks = if preemptible { None } else { Some(0) };
+        deferred
+    }
+
+    /// Count a timer preemption of the current task, warning once it reaches
+    /// `PREEMPT_WARN_THRESHOLD` without a syscall in between.
+    fn note_current_preempted(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = inner.current_tcb_mut();
+        task.preemptions += 1;
+        if task.preemptions == PREEMPT_WARN_THRESHOLD {
+            warn!(
+                "[kernel] app_{} preempted {} times without a syscall, possible infinite loop, last syscall {:?}",
+                current, PREEMPT_WARN_THRESHOLD, task.stats.last_syscall()
+            );
+        }
+    }
+
+    /// The current task entered the kernel on its own, so it is making progress.
+    fn reset_current_preemptions(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_tcb_mut().preemptions = 0;
+    }
+
+    /// Set the current 'Running' task's priority.
+    fn set_current_priority(&self, prio: isize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_tcb_mut().priority = prio;
+    }
+
+    /// The current task's name, entry point and where its image was loaded.
+    fn get_current_app_info(&self) -> (&'static str, usize, Range<usize>) {
+        let inner = self.inner.exclusive_access();
+        let task = inner.current_tcb();
+        (task.name, task.entry_point, task.image.clone())
+    }
+
+    /// The first page found taken by the current task's last mmap, if it
+    /// failed that way.
+    fn get_current_map_conflict(&self) -> Option<VirtPageNum> {
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().last_map_conflict
+    }
+
+    /// Give the current task a deadline `ms` from now, or none for 0.
+    fn set_current_deadline(&self, ms: usize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_tcb_mut().absolute_deadline = match ms {
+            0 => None,
+            ms => Some(get_time_ms().saturating_add(ms)),
+        };
+    }
+
+    /// The current task's priority and stride, read together.
+    fn get_current_sched_state(&self) -> (isize, usize) {
+        let inner = self.inner.exclusive_access();
+        let task = inner.current_tcb();
+        (task.priority, task.stride)
+    }
+
+    /// Get the current 'Running' task's resident and peak resident pages.
+    fn get_current_mem_usage(&self) -> (usize, usize) {
+        let inner = self.inner.exclusive_access();
+        let task = &inner.current_tcb();
+        (
+            task.memory_set.resident_pages(),
+            task.stats.peak_resident_pages,
+        )
+    }
+
+    /// Map `[start, start + len)` for the current task as `backing` says.
+    /// Returns 0 or an errno.
+    fn task_mmap(&self, start: usize, len: usize, port: usize, backing: MmapBacking) -> isize {
+        let (start_va, end_va, map_perm) =
+            match (user_range(start, len), MapPermission::from_port(port)) {
+                (Ok((start_va, end_va)), Some(map_perm)) => (start_va, end_va, map_perm),
+                _ => return EINVAL,
+            };
         let mut inner = self.inner.exclusive_access();
-        let current_task = inner.current_task;
-        let memory_set = &mut inner.tasks[current_task].memory_set;
+        let task = inner.current_tcb_mut();
+        let memory_set = &mut task.memory_set;
         let start_vpn = start_va.floor();
         let end_vpn = end_va.ceil();
-        for vpn in VPNRange::new(start_vpn, end_vpn) {
-            if let Some(pte) = memory_set.translate(vpn) {
-                if pte.is_valid() {
-                    return -1;
+        task.last_map_conflict = VPNRange::new(start_vpn, end_vpn)
+            .into_iter()
+            .find(|vpn| memory_set.is_reserved(*vpn));
+        if task.last_map_conflict.is_some() {
+            return EEXIST;
+        }
+        // the page tables come out of the same frames, and running out
+        // halfway would panic in `map_one`
+        if backing != MmapBacking::Lazy
+            && free_frame_count() < end_vpn.0 - start_vpn.0 + table_frames_for(start_vpn, end_vpn)
+        {
+            return ENOMEM;
+        }
+        match backing {
+            MmapBacking::Framed => memory_set.insert_framed_area(start_va, end_va, map_perm),
+            MmapBacking::Filled(fill) => {
+                memory_set.insert_filled_area(start_va, end_va, map_perm, fill)
+            }
+            MmapBacking::Lazy => memory_set.insert_lazy_area(start_va, end_va, map_perm),
+            MmapBacking::Uninit => memory_set.insert_uninit_area(start_va, end_va, map_perm),
+            MmapBacking::Huge => {
+                if !memory_set.insert_huge_area(start_va, end_va, map_perm) {
+                    return ENOMEM;
                 }
             }
         }
-        let map_perm = MapPermission::from_bits((port as u8) << 1).unwrap() | MapPermission::U;
-        memory_set.insert_framed_area(start_va, end_va, map_perm);
+        inner.current_tcb_mut().update_peak_resident();
         0
     }
 
+    /// Unmap `[start, start + len)` for the current task, returning 0 or an errno.
+    /// An end in the middle of a page is refused unless `MUNMAP_PARTIAL_END`,
+    /// which rounds it down instead.
     fn task_munmap(&self, start: usize, len: usize) -> isize {
-        let start_va = VirtAddr::from(start);
-        let end_va = VirtAddr::from(start + len);
-        if !start_va.aligned() {
-            return -1;
+        let (start_va, end_va) = match user_range(start, len) {
+            Ok((start_va, end_va)) if end_va.aligned() || MUNMAP_PARTIAL_END => (start_va, end_va),
+            _ => return EINVAL,
+        };
+        // the TrapContext frame is still in use through `trap_cx_ppn`
+        if end_va.0 > TRAP_CONTEXT {
+            return EINVAL;
         }
         let mut inner = self.inner.exclusive_access();
-        let current_task = inner.current_task;
-        let memory_set = &mut inner.tasks[current_task].memory_set;
+        let memory_set = &mut inner.current_tcb_mut().memory_set;
         let start_vpn = start_va.floor();
-        let end_vpn = end_va.ceil();
-        for vpn in VPNRange::new(start_vpn, end_vpn) {
-            if let Some(pte) = memory_set.translate(vpn) {
-                if !pte.is_valid() {
-                    return -1;
-                }
-            } else {
-                return -1;
-            }
+        let end_vpn = end_va.floor();
+        if !VPNRange::new(start_vpn, end_vpn)
+            .into_iter()
+            .all(|vpn| memory_set.in_area(vpn))
+            || memory_set.splits_huge_page(start_vpn, end_vpn)
+        {
+            return EINVAL;
         }
         memory_set.unmap(start_vpn, end_vpn);
         0
     }
+
+    /// Change the permission of `[start, start + len)` of the current task to
+    /// `port`, returning 0 or an errno.
+    fn task_mprotect(&self, start: usize, len: usize, port: usize) -> isize {
+        let (start_va, end_va, map_perm) =
+            match (user_range(start, len), MapPermission::from_port(port)) {
+                (Ok((start_va, end_va)), Some(map_perm)) => (start_va, end_va, map_perm),
+                _ => return EINVAL,
+            };
+        let mut inner = self.inner.exclusive_access();
+        let memory_set = &mut inner.current_tcb_mut().memory_set;
+        let start_vpn = start_va.floor();
+        let end_vpn = end_va.ceil();
+        if end_va.0 > TRAP_CONTEXT
+            || !VPNRange::new(start_vpn, end_vpn)
+                .into_iter()
+                .all(|vpn| memory_set.in_area(vpn))
+        {
+            return ENOMEM;
+        }
+        if memory_set.splits_huge_page(start_vpn, end_vpn) {
+            return EINVAL;
+        }
+        memory_set.protect(start_vpn, end_vpn, map_perm);
+        0
+    }
+
+    /// Pin (`lock`) or unpin `[start, start + len)` of the current task
+    /// against eviction, returning 0 or an errno.
+    fn task_set_locked(&self, start: usize, len: usize, lock: bool) -> isize {
+        let (start_va, end_va) = match user_range(start, len) {
+            Ok(range) => range,
+            Err(err) => return err,
+        };
+        let mut inner = self.inner.exclusive_access();
+        let memory_set = &mut inner.current_tcb_mut().memory_set;
+        let start_vpn = start_va.floor();
+        let end_vpn = end_va.ceil();
+        if end_va.0 > TRAP_CONTEXT
+            || !VPNRange::new(start_vpn, end_vpn)
+                .into_iter()
+                .all(|vpn| memory_set.in_area(vpn))
+        {
+            return ENOMEM;
+        }
+        memory_set.set_locked(start_vpn, end_vpn, lock);
+        0
+    }
+
+    /// Drop the resident frames of `[start, start + len)` for the current
+    /// task, keeping the range reserved. Returns 0 or an errno.
+    fn task_discard(&self, start: usize, len: usize) -> isize {
+        let (start_va, end_va) = match user_range(start, len) {
+            // dropping the TrapContext frame would leave `trap_cx_ppn` dangling
+            Ok((start_va, end_va)) if end_va.0 <= TRAP_CONTEXT => (start_va, end_va),
+            _ => return EINVAL,
+        };
+        let mut inner = self.inner.exclusive_access();
+        let memory_set = &mut inner.current_tcb_mut().memory_set;
+        let start_vpn = start_va.floor();
+        let end_vpn = end_va.ceil();
+        // megapages are never faulted in, a discarded one would stay gone
+        if !VPNRange::new(start_vpn, end_vpn)
+            .into_iter()
+            .all(|vpn| memory_set.in_area(vpn))
+            || memory_set.overlaps_huge(start_vpn, end_vpn)
+        {
+            return EINVAL;
+        }
+        memory_set.discard(start_vpn, end_vpn);
+        0
+    }
+
+    /// Panic if the kernel stack canary of the current task was overwritten.
+    fn check_current_kernel_stack(&self) {
+        let current = self.inner.exclusive_access().current_task;
+        if !kernel_stack_intact(current) {
+            panic!("kernel stack overflow for task {}", current);
+        }
+    }
+
+    /// Copy `src` into the current task's address space at `dst_va`.
+    fn copy_to_current_user(&self, dst_va: usize, src: &[u8]) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_tcb_mut().memory_set.copy_to_user(dst_va, src)
+    }
+
+    fn prepare_current_user(&self, va: usize, len: usize, access: MapPermission) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner
+            .current_tcb_mut()
+            .memory_set
+            .prepare_user_range(va, len, access)
+    }
+
+    /// Fill `dst` from the current task's address space at `src_va`.
+    fn copy_from_current_user(&self, dst: &mut [u8], src_va: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        inner
+            .current_tcb_mut()
+            .memory_set
+            .copy_from_user(dst, src_va)
+    }
+
+    /// Fill `dst` from the address space of task `pid` at `src_va`, returning
+    /// the length or an errno.
+    #[cfg(feature = "peek_task")]
+    fn peek_task(&self, pid: usize, dst: &mut [u8], src_va: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        match inner.tasks.get(pid) {
+            // an exited task has no user pages left
+            Some(task) if !task.task_status.is_terminated() => task.memory_set.peek(dst, src_va),
+            _ => ESRCH,
+        }
+    }
+
+    /// What `fd` of the current task refers to, if it is open.
+    fn get_current_fd(&self, fd: usize) -> Option<FileDescriptor> {
+        let inner = self.inner.exclusive_access();
+        inner.current_tcb().fd_table.get(fd).copied().flatten()
+    }
+
+    /// Open `file` on the lowest free fd of the current task, returning the
+    /// new fd or an errno.
+    fn open_current_fd(&self, file: FileDescriptor) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        match alloc_fd(&mut inner.current_tcb_mut().fd_table, file) {
+            Some(fd) => {
+                file.opened();
+                fd as isize
+            }
+            None => EMFILE,
+        }
+    }
+
+    /// Open the lowest free fd of the current task on what `fd` refers to.
+    /// Returns the new fd or an errno.
+    fn dup_current_fd(&self, fd: usize) -> isize {
+        match self.get_current_fd(fd) {
+            Some(file) => self.open_current_fd(file),
+            None => EINVAL,
+        }
+    }
+
+    /// Close `fd` of the current task, returning 0 or an errno.
+    fn close_current_fd(&self, fd: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        match inner.current_tcb_mut().fd_table.get_mut(fd) {
+            Some(slot @ Some(_)) => {
+                slot.take().unwrap().closed();
+                0
+            }
+            _ => EINVAL,
+        }
+    }
+
+    /// Residency of the pages in `[start, start + len)` of the current task.
+    fn task_mincore(&self, start: usize, len: usize) -> Result<Vec<u8>, isize> {
+        let (start_va, end_va) = match user_range(start, len) {
+            Ok((start_va, end_va)) if end_va.0 <= TRAP_CONTEXT => (start_va, end_va),
+            Ok(_) => return Err(ENOMEM),
+            Err(err) => return Err(err),
+        };
+        let inner = self.inner.exclusive_access();
+        let memory_set = &inner.current_tcb().memory_set;
+        memory_set
+            .residency(start_va.floor(), end_va.ceil())
+            .ok_or(ENOMEM)
+    }
+
+    /// Try to resolve a page fault of the current task at `addr`.
+    fn handle_current_page_fault(&self, addr: usize, access: MapPermission) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let task = &mut inner.current_tcb_mut();
+        let handled = task
+            .memory_set
+            .handle_page_fault(VirtAddr::from(addr).floor(), access);
+        if handled {
+            task.stats.page_faults += 1;
+            task.update_peak_resident();
+        }
+        handled
+    }
+}
+
+/// How `task_mmap_with` backs a new range.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MmapBacking {
+    /// zeroed 4KB frames, mapped right away
+    Framed,
+    /// 4KB frames filled with the byte, mapped right away
+    Filled(u8),
+    /// reserved only, zero pages are faulted in on first access
+    Lazy,
+    /// 4KB frames left as their last owner had them, mapped right away
+    Uninit,
+    /// 2MB megapages; the range must be megapage aligned
+    Huge,
 }
 
 /// Run the first task in task list.
@@ -244,9 +971,9 @@ fn mark_current_suspended() {
     TASK_MANAGER.mark_current_suspended();
 }
 
-/// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+/// Change the status of current `Running` task into `Exited` with `exit_code`.
+fn mark_current_exited(exit_code: i32) {
+    TASK_MANAGER.mark_current_exited(exit_code);
 }
 
 /// Suspend the current 'Running' task and run the next task in task list.
@@ -255,12 +982,79 @@ pub fn suspend_current_and_run_next() {
     run_next_task();
 }
 
-/// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+/// Suspend the current 'Running' task on a timer interrupt and run the next
+/// task, or the one the scheduler watchdog picks if switching has stalled.
+pub fn preempt_current_and_run_next() {
+    if TASK_MANAGER.defer_current_preemption() {
+        return;
+    }
+    TASK_MANAGER.note_current_preempted();
+    match TASK_MANAGER.watchdog_tick() {
+        Some(next) => {
+            mark_current_suspended();
+            TASK_MANAGER.run_task(Some(next));
+        }
+        None => suspend_current_and_run_next(),
+    }
+}
+
+/// The first `Ready` task after `current` in task list order, other than
+/// `current` itself.
+fn next_other_ready(tasks: &[TaskControlBlock], current: usize) -> Option<usize> {
+    (1..tasks.len())
+        .map(|offset| (current + offset) % tasks.len())
+        .find(|&id| tasks[id].task_status.is_runnable())
+}
+
+/// Count a timer tick taken while a task ran, for [`cpu_profile`].
+pub fn sample_cpu_profile() {
+    TASK_MANAGER.sample_cpu_profile();
+}
+
+#[allow(unused)]
+/// Sampled CPU usage so far: how many samples found each task running, by id.
+pub fn cpu_profile() -> [u64; MAX_TASKS] {
+    TASK_MANAGER.cpu_profile()
+}
+
+pub fn reset_current_preemptions() {
+    TASK_MANAGER.reset_current_preemptions();
+}
+
+/// Switch to a ready task from the idle loop.
+fn run_from_idle() {
+    TASK_MANAGER.run_from_idle();
+}
+
+/// Let the current 'Running' task sleep for `ms` milliseconds and run the next task.
+pub fn sleep_current_and_run_next(ms: usize) {
+    TASK_MANAGER.mark_current_sleeping(ms);
+    run_next_task();
+}
+
+/// Block the current 'Running' task until it is woken, and run the next task.
+pub fn block_current_and_run_next() {
+    TASK_MANAGER.mark_current_blocked();
+    run_next_task();
+}
+
+/// Wake the `Blocked` task `task_id`, returning 0 or an errno.
+pub fn wake_task(task_id: usize) -> isize {
+    TASK_MANAGER.wake_task(task_id)
+}
+
+/// Exit the current 'Running' task with `exit_code` and run the next task in
+/// task list.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    mark_current_exited(exit_code);
     run_next_task();
 }
 
+/// End every task and stop the run with `exit_code`.
+pub fn exit_group(exit_code: i32) -> ! {
+    TASK_MANAGER.exit_group(exit_code)
+}
+
 /// Get the current 'Running' task's token.
 pub fn current_user_token() -> usize {
     TASK_MANAGER.get_current_token()
@@ -272,29 +1066,601 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
 }
 
 // 计算系统调用次数
+/// Count `syscall_id` for the current task and note it as its last syscall.
 pub fn count_syscall(syscall_id: usize) {
     TASK_MANAGER.count_syscall(syscall_id);
 }
 
+/// Id of the syscall the current task made most recently, if any.
+pub fn get_current_last_syscall() -> Option<usize> {
+    TASK_MANAGER.get_current_last_syscall()
+}
+
 // 获取系统调用次数
 pub fn get_syscall_times() -> [u32; MAX_SYSCALL_NUM] {
     TASK_MANAGER.get_syscall_times()
 }
 
+pub fn get_current_task_stats() -> TaskStats {
+    TASK_MANAGER.get_current_task_stats()
+}
+
+/// Get the index of the current 'Running' task, which serves as its pid.
+pub fn get_current_task_id() -> usize {
+    TASK_MANAGER.get_current_task_id()
+}
+
+/// Get the id of the task that created the current one, if any.
+pub fn get_current_parent() -> Option<usize> {
+    TASK_MANAGER.get_current_parent()
+}
+
 // 获取任务状态
 pub fn get_current_task_status() -> TaskStatus {
     TASK_MANAGER.get_current_task_status()
 }
 
-// 获取任务运行时间
-pub fn get_current_run_time() -> usize {
-    TASK_MANAGER.get_current_run_time()
+/// Wall time since the current task first ran, in ms.
+pub fn get_current_elapsed_time() -> usize {
+    TASK_MANAGER.get_current_elapsed_time()
+}
+
+/// Whether the current 'Running' task has used up its time budget.
+pub fn current_exceeds_time_budget() -> bool {
+    TASK_MANAGER.current_exceeds_time_budget()
+}
+
+/// Set the current 'Running' task's priority.
+pub fn get_current_sched_state() -> (isize, usize) {
+    TASK_MANAGER.get_current_sched_state()
+}
+
+pub fn set_current_priority(prio: isize) {
+    TASK_MANAGER.set_current_priority(prio);
+}
+
+/// Hold off timer preemption of the current task, see `MAX_PREEMPT_DISABLE_TICKS`.
+pub fn disable_current_preemption() {
+    TASK_MANAGER.set_current_preemptible(false);
+}
+
+/// Let the current task be preempted again, switching away now if a
+/// preemption came while it was disabled. Returns the ticks held off.
+pub fn enable_current_preemption() -> usize {
+    let deferred = TASK_MANAGER.set_current_preemptible(true);
+    if deferred > 0 {
+        suspend_current_and_run_next();
+    }
+    deferred
+}
+
+/// Give the current task a deadline `ms` from now, or none for 0.
+pub fn set_current_deadline(ms: usize) {
+    TASK_MANAGER.set_current_deadline(ms);
+}
+
+/// Get the current 'Running' task's resident and peak resident pages.
+pub fn get_current_mem_usage() -> (usize, usize) {
+    TASK_MANAGER.get_current_mem_usage()
 }
 
 pub fn task_mmap(start: usize, len: usize, port: usize) -> isize {
-    TASK_MANAGER.task_mmap(start, len, port)
+    TASK_MANAGER.task_mmap(start, len, port, MmapBacking::Framed)
+}
+
+/// The current task's name, entry point and where its image was loaded.
+pub fn get_current_app_info() -> (&'static str, usize, Range<usize>) {
+    TASK_MANAGER.get_current_app_info()
+}
+
+/// The first page found taken by the current task's last mmap, if it failed
+/// that way.
+pub fn get_current_map_conflict() -> Option<VirtPageNum> {
+    TASK_MANAGER.get_current_map_conflict()
+}
+
+/// Like `task_mmap`, with the pages backed as `backing` says.
+pub fn task_mmap_with(start: usize, len: usize, port: usize, backing: MmapBacking) -> isize {
+    TASK_MANAGER.task_mmap(start, len, port, backing)
 }
 
 pub fn task_munmap(start: usize, len: usize) -> isize {
     TASK_MANAGER.task_munmap(start, len)
 }
+
+pub fn task_mlock(start: usize, len: usize) -> isize {
+    TASK_MANAGER.task_set_locked(start, len, true)
+}
+
+pub fn task_munlock(start: usize, len: usize) -> isize {
+    TASK_MANAGER.task_set_locked(start, len, false)
+}
+
+pub fn task_mprotect(start: usize, len: usize, port: usize) -> isize {
+    TASK_MANAGER.task_mprotect(start, len, port)
+}
+
+pub fn get_current_fd(fd: usize) -> Option<FileDescriptor> {
+    TASK_MANAGER.get_current_fd(fd)
+}
+
+pub fn open_current_fd(file: FileDescriptor) -> isize {
+    TASK_MANAGER.open_current_fd(file)
+}
+
+pub fn dup_current_fd(fd: usize) -> isize {
+    TASK_MANAGER.dup_current_fd(fd)
+}
+
+pub fn close_current_fd(fd: usize) -> isize {
+    TASK_MANAGER.close_current_fd(fd)
+}
+
+/// One byte per page of `[start, start + len)`, 1 if resident, or an errno.
+pub fn task_mincore(start: usize, len: usize) -> Result<Vec<u8>, isize> {
+    TASK_MANAGER.task_mincore(start, len)
+}
+
+pub fn task_discard(start: usize, len: usize) -> isize {
+    TASK_MANAGER.task_discard(start, len)
+}
+
+/// Copy `src` to the current task at `dst_va`, returning the length or `EFAULT`.
+pub fn copy_to_current_user(dst_va: usize, src: &[u8]) -> isize {
+    TASK_MANAGER.copy_to_current_user(dst_va, src)
+}
+
+/// Whether `[va, va + len)` of the current task is user memory allowing
+/// `access`, faulting in reserved lazy pages on the way.
+pub fn prepare_current_user(va: usize, len: usize, access: MapPermission) -> bool {
+    TASK_MANAGER.prepare_current_user(va, len, access)
+}
+
+/// Fill `dst` from the current task at `src_va`, returning the length or `EFAULT`.
+pub fn copy_from_current_user(dst: &mut [u8], src_va: usize) -> isize {
+    TASK_MANAGER.copy_from_current_user(dst, src_va)
+}
+
+/// Fill `dst` from task `pid` at `src_va`, returning the length or an errno.
+#[cfg(feature = "peek_task")]
+pub fn peek_task(pid: usize, dst: &mut [u8], src_va: usize) -> isize {
+    TASK_MANAGER.peek_task(pid, dst, src_va)
+}
+
+pub fn handle_current_page_fault(addr: usize, access: MapPermission) -> bool {
+    TASK_MANAGER.handle_current_page_fault(addr, access)
+}
+
+pub fn check_current_kernel_stack() {
+    TASK_MANAGER.check_current_kernel_stack();
+}
+
+/// Pick the id in `ready` with the highest priority plus boost, `aged` holding
+/// `(priority, boost)` by task id. Ties go to the earliest in `ready`. The
+/// winner's boost is reset; every other ready task gains one, up to the cap.
+fn pick_aged(ready: &[usize], aged: &mut [(isize, isize)]) -> Option<usize> {
+    let mut next: Option<usize> = None;
+    for &id in ready {
+        let effective = |id: usize| aged[id].0 + aged[id].1;
+        if next.map_or(true, |best| effective(id) > effective(best)) {
+            next = Some(id);
+        }
+    }
+    for &id in ready {
+        let boost = &mut aged[id].1;
+        *boost = if Some(id) == next {
+            0
+        } else {
+            (*boost + 1).min(PRIORITY_AGING_CAP)
+        };
+    }
+    next
+}
+
+/// Pick the id in `ready` with the nearest deadline, `deadlines` holding
+/// them by task id. Tasks without one come after every task with one, and
+/// ties go to the earliest in `ready`.
+fn pick_earliest_deadline(ready: &[usize], deadlines: &[Option<usize>]) -> Option<usize> {
+    ready.iter().copied().min_by_key(|id| match deadlines[*id] {
+        Some(deadline) => (0, deadline),
+        None => (1, 0),
+    })
+}
+
+#[allow(unused)]
+/// the earlier of two deadlines wins whatever the scan order, and a task
+/// without a deadline only runs when no other is ready
+pub fn earliest_deadline_test() {
+    let deadlines = [None, Some(300), Some(100)];
+    assert_eq!(pick_earliest_deadline(&[0, 1, 2], &deadlines), Some(2));
+    assert_eq!(pick_earliest_deadline(&[1, 0], &deadlines), Some(1));
+    assert_eq!(pick_earliest_deadline(&[0], &deadlines), Some(0));
+    // equal deadlines keep the scan order
+    let deadlines = [Some(100), Some(100)];
+    assert_eq!(pick_earliest_deadline(&[1, 0], &deadlines), Some(1));
+    assert_eq!(pick_earliest_deadline(&[], &deadlines), None);
+    info!("earliest_deadline_test passed!");
+}
+
+#[allow(unused)]
+/// under aging a low-priority task still runs next to an always-ready hog,
+/// and a boost never grows past the cap
+pub fn priority_aging_test() {
+    use crate::config::DEFAULT_PRIORITY;
+    let (hog, low) = (0, 1);
+    let mut aged = [(DEFAULT_PRIORITY + 8, 0), (DEFAULT_PRIORITY, 0)];
+    let mut picks = Vec::new();
+    for _ in 0..19 {
+        picks.push(pick_aged(&[hog, low], &mut aged).unwrap());
+    }
+    // the hog wins until the low task has been skipped 9 times
+    assert!(picks[..9].iter().all(|id| *id == hog));
+    assert_eq!(picks[9], low);
+    assert_eq!(
+        aged[low].1,
+        picks[10..].iter().filter(|id| **id == hog).count() as isize
+    );
+    // out of reach of the cap, the boost saturates
+    let mut aged = [
+        (DEFAULT_PRIORITY + PRIORITY_AGING_CAP + 1, 0),
+        (DEFAULT_PRIORITY, 0),
+    ];
+    for _ in 0..2 * PRIORITY_AGING_CAP {
+        assert_eq!(pick_aged(&[hog, low], &mut aged), Some(hog));
+    }
+    assert_eq!(aged[low].1, PRIORITY_AGING_CAP);
+    assert_eq!(pick_aged(&[], &mut aged), None);
+    info!("priority_aging_test passed!");
+}
+
+#[allow(unused)]
+/// panics on purpose, run it last: a corrupted `current_task` is reported
+/// as "current_task 64 out of range, only N tasks" rather than a bare index
+/// out of bounds
+pub fn current_tcb_bounds_test() {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    assert!(inner.current_task < inner.tasks.len());
+    inner.current_task = 64.max(inner.tasks.len());
+    let _ = inner.current_tcb().task_status;
+    unreachable!("current_tcb_bounds_test expects a panic above");
+}
+
+#[allow(unused)]
+/// the canary check notices a clobbered stack bottom; run before the first task
+pub fn kernel_stack_canary_test() {
+    let app_id = TASK_MANAGER.num_app - 1;
+    assert!(kernel_stack_intact(app_id));
+    let (kernel_stack_bottom, _) = crate::config::kernel_stack_position(app_id);
+    unsafe {
+        (kernel_stack_bottom as *mut usize).write_volatile(0);
+    }
+    assert!(!kernel_stack_intact(app_id));
+    write_kernel_stack_canary(app_id);
+    assert!(kernel_stack_intact(app_id));
+    info!("kernel_stack_canary_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task: `FIRST_TASK_ID` (set it to 1 to try) is the
+/// one about to start, and no other task has run yet
+pub fn first_task_test() {
+    let inner = TASK_MANAGER.inner.exclusive_access();
+    assert_eq!(inner.current_task, FIRST_TASK_ID);
+    assert!(inner.tasks[FIRST_TASK_ID].task_status == TaskStatus::Ready);
+    assert!(inner.tasks.iter().all(|task| task.start_time.is_none()));
+    info!("first_task_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task: only the slices a task spends `Running` count
+/// towards its run time, not those other tasks run in while it is preempted
+pub fn run_time_accounting_test() {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let task = &mut inner.tasks[0];
+    let saved = (task.task_status, task.last_scheduled, task.stats.run_time);
+    // ran for 20 ms, then switched in again at 1000 ms
+    task.stats.run_time = 20;
+    task.last_scheduled = 1000;
+    task.task_status = TaskStatus::Running;
+    assert_eq!(task.total_run_time(1050), 70);
+    // preempted at 1050, others ran until 2000
+    task.stats.run_time = task.total_run_time(1050);
+    task.task_status = TaskStatus::Ready;
+    assert_eq!(task.total_run_time(2000), 70);
+    task.last_scheduled = 2000;
+    task.task_status = TaskStatus::Running;
+    assert_eq!(task.total_run_time(2010), 80);
+    (task.task_status, task.last_scheduled, task.stats.run_time) = saved;
+    info!("run_time_accounting_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task: a syscall counter stops at `u32::MAX`
+pub fn syscall_times_saturate_test() {
+    const SYSCALL_GETPID: usize = 172;
+    let saved = *TASK_MANAGER.inner.exclusive_access().current_tcb().stats;
+    TASK_MANAGER
+        .inner
+        .exclusive_access()
+        .current_tcb_mut()
+        .stats
+        .syscall_times[SYSCALL_GETPID] = u32::MAX - 1;
+    for _ in 0..3 {
+        TASK_MANAGER.count_syscall(SYSCALL_GETPID);
+    }
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    assert_eq!(
+        inner.current_tcb().stats.syscall_times[SYSCALL_GETPID],
+        u32::MAX
+    );
+    *inner.current_tcb_mut().stats = saved;
+    info!("syscall_times_saturate_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task, with at least two apps: ticks without a switch
+/// to another `Ready` task trip the watchdog, which names a task to switch
+/// to, and a real switch clears the count
+pub fn sched_watchdog_test() {
+    let current = TASK_MANAGER.inner.exclusive_access().current_task;
+    let other = next_other_ready(&TASK_MANAGER.inner.exclusive_access().tasks, current).unwrap();
+    assert_ne!(other, current);
+    // a missed switch: the timer keeps firing, nothing switches
+    for _ in 0..SCHED_WATCHDOG_TICKS {
+        assert_eq!(TASK_MANAGER.watchdog_tick(), None);
+    }
+    assert_eq!(TASK_MANAGER.watchdog_tick(), Some(other));
+    assert_eq!(TASK_MANAGER.inner.exclusive_access().stalled_ticks, 0);
+    // a switch to another task clears the count
+    TASK_MANAGER.watchdog_tick();
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let task = &inner.tasks[other];
+    let saved = (
+        task.task_status,
+        task.stats.schedules,
+        task.stride,
+        task.start_time,
+        task.last_scheduled,
+    );
+    inner.switch_in(other, get_time_ms());
+    assert_eq!(inner.stalled_ticks, 0);
+    inner.current_task = current;
+    let task = &mut inner.tasks[other];
+    (
+        task.task_status,
+        task.stats.schedules,
+        task.stride,
+        task.start_time,
+        task.last_scheduled,
+    ) = saved;
+    info!("sched_watchdog_test passed!");
+}
+
+#[allow(unused)]
+/// picking the current task as the next one starts a new slice without a
+/// `__switch`, which would overwrite its saved context with the caller's
+pub fn self_switch_test() {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let current = inner.current_task;
+    let task = inner.current_tcb();
+    let saved = (
+        task.task_cx,
+        task.task_status,
+        task.stats.schedules,
+        task.stats.run_time,
+        task.stride,
+        task.start_time,
+        task.last_scheduled,
+    );
+    drop(inner);
+    TASK_MANAGER.run_task(Some(current));
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    assert_eq!(inner.current_task, current);
+    let task = inner.current_tcb_mut();
+    assert!(task.task_cx == saved.0);
+    assert_eq!(task.task_status, TaskStatus::Running);
+    assert_eq!(task.stats.schedules, saved.2 + 1);
+    (
+        task.task_cx,
+        task.task_status,
+        task.stats.schedules,
+        task.stats.run_time,
+        task.stride,
+        task.start_time,
+        task.last_scheduled,
+    ) = saved;
+    info!("self_switch_test passed!");
+}
+
+#[allow(unused)]
+/// a boot order is followed pick by pick, skipping tasks that cannot run,
+/// and the policy takes over once it is used up; needs at least 3 apps
+pub fn boot_order_test() {
+    let num_app = TASK_MANAGER.num_app;
+    let current = TASK_MANAGER.inner.exclusive_access().current_task;
+    let (a, b) = ((current + 2) % num_app, (current + 1) % num_app);
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    inner.boot_order = [a, b, a].iter().copied().collect();
+    let saved = inner.tasks[b].task_status;
+    drop(inner);
+    assert_eq!(TASK_MANAGER.find_next_task(), Some(a));
+    TASK_MANAGER.inner.exclusive_access().tasks[b].task_status = TaskStatus::Exited;
+    // `b` is skipped, so `a` comes again
+    assert_eq!(TASK_MANAGER.find_next_task(), Some(a));
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    assert!(inner.boot_order.is_empty());
+    inner.tasks[b].task_status = saved;
+    drop(inner);
+    if SCHED_POLICY == SchedPolicy::RoundRobin {
+        assert_eq!(TASK_MANAGER.find_next_task(), Some(b));
+    }
+    info!("boot_order_test passed!");
+}
+
+#[allow(unused)]
+/// only `Ready` tasks are runnable, so a blocked task next in line is
+/// passed over by the scheduler
+pub fn scheduler_skips_blocked_test() {
+    for (status, runnable, terminated) in [
+        (TaskStatus::UnInit, false, false),
+        (TaskStatus::Ready, true, false),
+        (TaskStatus::Running, false, false),
+        (TaskStatus::Sleeping, false, false),
+        (TaskStatus::Blocked, false, false),
+        (TaskStatus::Exited, false, true),
+    ] {
+        assert_eq!(status.is_runnable(), runnable);
+        assert_eq!(status.is_terminated(), terminated);
+    }
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let current = inner.current_task;
+    let blocked = next_other_ready(&inner.tasks, current).unwrap();
+    let saved = inner.tasks[blocked].task_status;
+    inner.tasks[blocked].task_status = TaskStatus::Blocked;
+    let boot_order = core::mem::take(&mut inner.boot_order);
+    drop(inner);
+    assert_ne!(TASK_MANAGER.find_next_task(), Some(blocked));
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    inner.tasks[blocked].task_status = saved;
+    inner.boot_order = boot_order;
+    info!("scheduler_skips_blocked_test passed!");
+}
+
+#[allow(unused)]
+/// ticks are held off while preemption is disabled and reported on enable,
+/// up to the limit past which the task is preempted anyway
+pub fn preempt_disable_test() {
+    assert!(!TASK_MANAGER.defer_current_preemption());
+    TASK_MANAGER.set_current_preemptible(false);
+    for _ in 0..3 {
+        assert!(TASK_MANAGER.defer_current_preemption());
+    }
+    assert_eq!(TASK_MANAGER.set_current_preemptible(true), 3);
+    assert!(!TASK_MANAGER.defer_current_preemption());
+    // an app that never enables it again
+    TASK_MANAGER.set_current_preemptible(false);
+    for _ in 0..MAX_PREEMPT_DISABLE_TICKS {
+        assert!(TASK_MANAGER.defer_current_preemption());
+    }
+    assert!(!TASK_MANAGER.defer_current_preemption());
+    assert!(!TASK_MANAGER.defer_current_preemption());
+    assert_eq!(TASK_MANAGER.set_current_preemptible(true), 0);
+    info!("preempt_disable_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task, with at least two apps: two tasks taking
+/// turns every tick end up with the same share of the samples
+pub fn cpu_profile_balance_test() {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let current = inner.current_task;
+    let other = next_other_ready(&inner.tasks, current).unwrap();
+    let saved = (inner.profile_ticks, inner.cpu_profile);
+    inner.profile_ticks = 0;
+    inner.cpu_profile = [0; MAX_TASKS];
+    let ticks = PROFILE_SAMPLE_TICKS * 100;
+    for tick in 0..ticks {
+        inner.current_task = if tick % 2 == 0 { current } else { other };
+        inner.sample_cpu_profile();
+    }
+    let (a, b) = (inner.cpu_profile[current], inner.cpu_profile[other]);
+    assert_eq!(a + b, 100);
+    assert!(
+        a.max(b) - a.min(b) <= 10,
+        "unbalanced samples {} vs {}",
+        a,
+        b
+    );
+    inner.current_task = current;
+    (inner.profile_ticks, inner.cpu_profile) = saved;
+    info!("cpu_profile_balance_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task, with at least three apps: a forced switch
+/// goes where the policy says, never back to the stalled task
+pub fn watchdog_follows_policy_test() {
+    use crate::config::DEFAULT_PRIORITY;
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let current = inner.current_task;
+    let saved_status = inner.tasks[current].task_status;
+    // the stalled task would win on priority if it were a candidate
+    inner.tasks[current].task_status = TaskStatus::Ready;
+    let after = next_other_ready(&inner.tasks, current).unwrap();
+    let favored = next_other_ready(&inner.tasks, after).unwrap();
+    assert!(favored != current && favored != after);
+    let saved: Vec<(isize, isize)> = inner
+        .tasks
+        .iter()
+        .map(|task| (task.priority, task.priority_boost))
+        .collect();
+    inner.tasks[current].priority = DEFAULT_PRIORITY + 2;
+    inner.tasks[favored].priority = DEFAULT_PRIORITY + 1;
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::Priority, Some(current)),
+        Some(favored)
+    );
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::RoundRobin, Some(current)),
+        Some(after)
+    );
+    inner.tasks[current].task_status = saved_status;
+    for (task, (priority, boost)) in inner.tasks.iter_mut().zip(saved) {
+        task.priority = priority;
+        task.priority_boost = boost;
+    }
+    info!("watchdog_follows_policy_test passed!");
+}
+
+#[allow(unused)]
+/// run before the first task, with at least three apps: round robin takes
+/// the next `Ready` task after the current one, stride the smallest stride
+/// with ties going to the next in round robin order
+pub fn sched_policy_test() {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let num_app = inner.tasks.len();
+    let current = inner.current_task;
+    let saved: Vec<(TaskStatus, usize)> = inner
+        .tasks
+        .iter()
+        .map(|task| (task.task_status, task.stride))
+        .collect();
+    let (next, after) = ((current + 1) % num_app, (current + 2) % num_app);
+    for task in inner.tasks.iter_mut() {
+        task.task_status = TaskStatus::Ready;
+        task.stride = BIG_STRIDE;
+    }
+    inner.tasks[current].task_status = TaskStatus::Running;
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::RoundRobin, None),
+        Some(next)
+    );
+    assert_eq!(inner.pick_by_policy(SchedPolicy::Stride, None), Some(next));
+    inner.tasks[after].stride = 0;
+    assert_eq!(inner.pick_by_policy(SchedPolicy::Stride, None), Some(after));
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::RoundRobin, None),
+        Some(next)
+    );
+    inner.tasks[next].task_status = TaskStatus::Blocked;
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::RoundRobin, None),
+        Some(after)
+    );
+    // a suspended current task comes last in round robin order
+    inner.tasks[current].task_status = TaskStatus::Ready;
+    inner.tasks[after].task_status = TaskStatus::Blocked;
+    let expected = (current + 3..current + num_app + 1)
+        .map(|id| id % num_app)
+        .next()
+        .unwrap();
+    assert_eq!(
+        inner.pick_by_policy(SchedPolicy::RoundRobin, None),
+        Some(expected)
+    );
+    for (task, (status, stride)) in inner.tasks.iter_mut().zip(saved) {
+        task.task_status = status;
+        task.stride = stride;
+    }
+    info!("sched_policy_test passed!");
+}
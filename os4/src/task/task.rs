@@ -1,9 +1,16 @@
 //! Types related to task management
 use super::TaskContext;
-use crate::config::{kernel_stack_position, MAX_SYSCALL_NUM, TRAP_CONTEXT};
-use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::config::{
+    kernel_stack_position, DEFAULT_PRIORITY, KERNEL_STACK_CANARY, MAX_SYSCALL_NUM, TRAP_CONTEXT,
+};
+use crate::fs::{default_fd_table, FileDescriptor};
+use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, VirtPageNum, KERNEL_SPACE};
 use crate::trap::{trap_handler, TrapContext};
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
 
 /// task control block structure
 pub struct TaskControlBlock {
@@ -12,8 +19,40 @@ pub struct TaskControlBlock {
     pub memory_set: MemorySet,
     pub trap_cx_ppn: PhysPageNum,
     pub base_size: usize,
-    pub syscall_times: Box<[u32; MAX_SYSCALL_NUM]>, // 系统调用次数
-    pub start_time: Option<usize>,                  //
+    /// counters reported by `sys_task_stats`
+    pub stats: Box<TaskStats>,
+    /// when the task first ran, in ms
+    pub start_time: Option<usize>,
+    pub priority: isize,
+    pub stride: usize,
+    /// when the task was last switched in, in ms
+    pub last_scheduled: usize,
+    /// timer preemptions since the task last made a syscall
+    pub preemptions: usize,
+    /// timer ticks held off since `sys_preempt_disable`, `None` while
+    /// preemption is enabled; above 0 a preemption is pending
+    pub deferred_ticks: Option<usize>,
+    /// when a `Sleeping` task becomes ready again, in ms
+    pub wake_at: usize,
+    /// priority gained by being passed over, see `PRIORITY_AGING_CAP`
+    pub priority_boost: isize,
+    /// id of the task that created this one, `None` for apps loaded at boot
+    pub parent: Option<usize>,
+    /// open files by fd, `None` for a closed slot
+    pub fd_table: Vec<Option<FileDescriptor>>,
+    /// when the task wants to be done, in ms, set by `sys_set_deadline`
+    pub absolute_deadline: Option<usize>,
+    /// name of the app in the loader's table
+    pub name: &'static str,
+    pub entry_point: usize,
+    /// pages the elf segments were loaded at
+    pub image: Range<usize>,
+    /// first page found already taken by the last mmap of a valid range,
+    /// for `sys_last_map_error`
+    pub last_map_conflict: Option<VirtPageNum>,
+    /// what the task exited with, given to `sys_exit` or set by the kernel
+    /// when it killed the task; `None` until it is `Exited`
+    pub exit_code: Option<i32>,
 }
 
 impl TaskControlBlock {
@@ -23,9 +62,49 @@ impl TaskControlBlock {
     pub fn get_user_token(&self) -> usize {
         self.memory_set.token()
     }
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
+    /// Total time spent `Running` up to `now`, including the current time slice.
+    pub fn total_run_time(&self, now: usize) -> usize {
+        if self.task_status == TaskStatus::Running {
+            self.stats.run_time + now - self.last_scheduled
+        } else {
+            self.stats.run_time
+        }
+    }
+    /// Give back what an exited task holds: its user frames, as the TCB slot
+    /// is never dropped, and its fds, so a pipe it was writing to reaches
+    /// end of file.
+    pub fn release_resources(&mut self) {
+        self.memory_set.unmap_all_user();
+        for file in self.fd_table.drain(..).flatten() {
+            file.closed();
+        }
+    }
+    /// Drop the deadline if it has passed by `now`, returning by how many ms
+    /// it was missed.
+    pub fn take_missed_deadline(&mut self, now: usize) -> Option<usize> {
+        let deadline = self.absolute_deadline.filter(|deadline| *deadline < now)?;
+        self.absolute_deadline = None;
+        Some(now - deadline)
+    }
+    /// Record the current resident size if it is a new peak.
+    pub fn update_peak_resident(&mut self) {
+        self.stats.peak_resident_pages = self
+            .stats
+            .peak_resident_pages
+            .max(self.memory_set.resident_pages());
+    }
+    /// A copy of the counters as of `now`, with the run time and resident
+    /// size brought up to date.
+    pub fn stats_snapshot(&self, now: usize) -> TaskStats {
+        let mut stats = *self.stats;
+        stats.run_time = self.total_run_time(now);
+        stats.resident_pages = self.memory_set.resident_pages();
+        stats
+    }
+    /// Load the app `name` into a new task, failing if its elf is rejected.
+    pub fn new(name: &'static str, elf_data: &[u8], app_id: usize) -> Result<Self, &'static str> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point, image) = MemorySet::from_elf(elf_data)?;
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -38,14 +117,34 @@ impl TaskControlBlock {
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
         );
+        write_kernel_stack_canary(app_id);
+        let stats = Box::new(TaskStats {
+            peak_resident_pages: memory_set.resident_pages(),
+            ..TaskStats::zero_init()
+        });
         let task_control_block = Self {
             task_status,
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
             memory_set,
             trap_cx_ppn,
             base_size: user_sp,
-            syscall_times: Box::new([0; MAX_SYSCALL_NUM]),
+            stats,
             start_time: None,
+            priority: DEFAULT_PRIORITY,
+            stride: 0,
+            last_scheduled: 0,
+            preemptions: 0,
+            deferred_ticks: None,
+            wake_at: 0,
+            priority_boost: 0,
+            parent: None,
+            fd_table: default_fd_table(),
+            absolute_deadline: None,
+            name,
+            entry_point,
+            image,
+            last_map_conflict: None,
+            exit_code: None,
         };
         // prepare TrapContext in user space
         let trap_cx = task_control_block.get_trap_cx();
@@ -56,15 +155,146 @@ impl TaskControlBlock {
             kernel_stack_top,
             trap_handler as usize,
         );
-        task_control_block
+        Ok(task_control_block)
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// Per-task resource counters, copied out as a whole by `sys_task_stats`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TaskStats {
+    pub syscall_times: [u32; MAX_SYSCALL_NUM], // 系统调用次数
+    /// page faults resolved by mapping a lazily reserved page
+    pub page_faults: usize,
+    /// time spent `Running`, in ms; only up to the last switch-out on the TCB
+    pub run_time: usize,
+    /// frames currently held, only filled in a snapshot
+    pub resident_pages: usize,
+    /// the most frames this task's memory set has ever held
+    pub peak_resident_pages: usize,
+    /// times the task was switched in
+    pub schedules: usize,
+    /// id of the syscall made most recently, still running if the task is
+    /// blocked in it; `NO_SYSCALL` before the first
+    pub last_syscall: usize,
+}
+
+/// `TaskStats::last_syscall` of a task that has made no syscall yet
+pub const NO_SYSCALL: usize = usize::MAX;
+
+impl TaskStats {
+    pub fn zero_init() -> Self {
+        Self {
+            syscall_times: [0; MAX_SYSCALL_NUM],
+            page_faults: 0,
+            run_time: 0,
+            resident_pages: 0,
+            peak_resident_pages: 0,
+            schedules: 0,
+            last_syscall: NO_SYSCALL,
+        }
+    }
+    pub fn last_syscall(&self) -> Option<usize> {
+        match self.last_syscall {
+            NO_SYSCALL => None,
+            id => Some(id),
+        }
+    }
+}
+
+/// Place the canary at the bottom of the kernel stack of `app_id`.
+pub fn write_kernel_stack_canary(app_id: usize) {
+    let (kernel_stack_bottom, _) = kernel_stack_position(app_id);
+    unsafe {
+        (kernel_stack_bottom as *mut usize).write_volatile(KERNEL_STACK_CANARY);
+    }
+}
+
+/// Whether the canary of the kernel stack of `app_id` is still in place.
+pub fn kernel_stack_intact(app_id: usize) -> bool {
+    let (kernel_stack_bottom, _) = kernel_stack_position(app_id);
+    unsafe { (kernel_stack_bottom as *const usize).read_volatile() == KERNEL_STACK_CANARY }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// task status: UnInit, Ready, Running, Sleeping, Blocked, Exited
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
+    /// waiting in `sys_sleep` until `wake_at`
+    Sleeping,
+    /// waiting in `sys_pause` for a `sys_notify`
+    Blocked,
     Exited,
 }
+
+impl TaskStatus {
+    /// Whether the scheduler may pick a task in this state. Only `Ready`
+    /// is: a waiting state has to be made `Ready` by whatever it waits on.
+    pub fn is_runnable(&self) -> bool {
+        *self == TaskStatus::Ready
+    }
+    /// Whether the task is done for good.
+    pub fn is_terminated(&self) -> bool {
+        *self == TaskStatus::Exited
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::UnInit => "UnInit",
+            TaskStatus::Ready => "Ready",
+            TaskStatus::Running => "Running",
+            TaskStatus::Sleeping => "Sleeping",
+            TaskStatus::Blocked => "Blocked",
+            TaskStatus::Exited => "Exited",
+        }
+    }
+}
+
+impl Display for TaskStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[allow(unused)]
+/// every status formats to its name
+pub fn task_status_display_test() {
+    for (status, name) in [
+        (TaskStatus::UnInit, "UnInit"),
+        (TaskStatus::Ready, "Ready"),
+        (TaskStatus::Running, "Running"),
+        (TaskStatus::Sleeping, "Sleeping"),
+        (TaskStatus::Blocked, "Blocked"),
+        (TaskStatus::Exited, "Exited"),
+    ] {
+        assert_eq!(format!("{}", status), name);
+        assert_eq!(format!("{:?}", status), name);
+    }
+    info!("task_status_display_test passed!");
+}
+
+#[allow(unused)]
+/// kernel stacks of any configured size stay inside their slot, below a guard page
+pub fn kernel_stack_layout_test() {
+    use crate::config::{kernel_stack_size, PAGE_SIZE, TRAMPOLINE};
+    let mut higher_bottom = TRAMPOLINE;
+    // run once the tasks are loaded; set an override to try a 2-page stack
+    for app_id in 0..super::TASK_MANAGER.num_app {
+        let (bottom, top) = kernel_stack_position(app_id);
+        assert_eq!(top - bottom, kernel_stack_size(app_id));
+        assert!(kernel_stack_size(app_id) % PAGE_SIZE == 0);
+        assert!(top + PAGE_SIZE <= higher_bottom || app_id == 0);
+        higher_bottom = bottom;
+        // mapped from the bottom up to the top, with nothing right below
+        let kernel_space = KERNEL_SPACE.lock();
+        let mapped = |va: usize| {
+            kernel_space
+                .translate(VirtAddr::from(va).floor())
+                .map_or(false, |pte| pte.is_valid())
+        };
+        assert!(mapped(bottom) && mapped(top - 1));
+        assert!(!mapped(bottom - 1));
+    }
+    info!("kernel_stack_layout_test passed!");
+}
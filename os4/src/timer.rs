@@ -1,20 +1,40 @@
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MICRO_PER_SEC: usize = 1_000_000;
+const NANO_PER_SEC: usize = 1_000_000_000;
+
+/// `time` at which the pending timer interrupt ends the current time slice
+static NEXT_TRIGGER: AtomicUsize = AtomicUsize::new(0);
 
 pub fn get_time() -> usize {
     time::read()
 }
 
+/// length of one `time` tick in ns, rounded up
+pub fn get_resolution_ns() -> usize {
+    (NANO_PER_SEC + CLOCK_FREQ - 1) / CLOCK_FREQ
+}
+
 pub fn get_time_us() -> usize {
     time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
 }
 
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let next = get_time() + CLOCK_FREQ / TICKS_PER_SEC;
+    NEXT_TRIGGER.store(next, Ordering::Relaxed);
+    set_timer(next);
+}
+
+/// time left until the next timer interrupt, in us
+pub fn time_slice_left_us() -> usize {
+    NEXT_TRIGGER
+        .load(Ordering::Relaxed)
+        .saturating_sub(get_time())
+        / (CLOCK_FREQ / MICRO_PER_SEC)
 }
 
 pub fn get_time_ms() -> usize {
@@ -17,6 +17,10 @@ impl TrapContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.x[2] = sp;
     }
+    /// Whether `sret` with this context drops to U-mode, as it must for an app.
+    pub fn returns_to_user(&self) -> bool {
+        self.sstatus.spp() == SPP::User
+    }
     pub fn app_init_context(
         entry: usize,
         sp: usize,
@@ -13,18 +13,34 @@
 //! to [`syscall()`].
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::{
+    APP_TIME_BUDGET_MS, ILLEGAL_INSTRUCTION_EXIT_CODE, PAGE_FAULT_EXIT_CODE, TIME_BUDGET_EXIT_CODE,
+    TRAMPOLINE, TRAP_CONTEXT,
+};
+use crate::mm::MapPermission;
+#[cfg(debug_assertions)]
+use crate::mm::{current_satp_is_valid, satp_is_valid};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    check_current_kernel_stack, current_exceeds_time_budget, current_trap_cx, current_user_token,
+    exit_current_and_run_next, get_current_last_syscall, handle_current_page_fault,
+    preempt_current_and_run_next, reset_current_preemptions, sample_cpu_profile,
 };
 use crate::timer::set_next_trigger;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sepc, sie, stval, stvec,
 };
 
+/// `scause` value of an `ecall` from S-mode, which `Exception` has no variant for
+const SUPERVISOR_ENV_CALL: usize = 9;
+
+/// Whether `scause` reports an `ecall` made by the kernel itself.
+fn is_supervisor_ecall(scause: &scause::Scause) -> bool {
+    scause.is_exception() && scause.code() == SUPERVISOR_ENV_CALL
+}
+
 core::arch::global_asm!(include_str!("trap.S"));
 
 pub fn init() {
@@ -52,27 +68,49 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    check_current_kernel_stack();
     let cx = current_trap_cx();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            reset_current_preemptions();
+            let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+            cx.x[10] = syscall(cx.x[17], args) as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+            if handle_current_page_fault(stval, MapPermission::W) => {}
+        Trap::Exception(Exception::LoadPageFault)
+            if handle_current_page_fault(stval, MapPermission::R) => {}
+        // lazily reserved code, or text dropped with `MADV_DONTNEED`
+        Trap::Exception(Exception::InstructionPageFault)
+            if handle_current_page_fault(stval, MapPermission::X) => {}
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
             error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.", stval, cx.sepc);
-            exit_current_and_run_next();
+            exit_current_and_run_next(PAGE_FAULT_EXIT_CODE);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             error!("[kernel] IllegalInstruction in application, core dumped.");
-            exit_current_and_run_next();
+            exit_current_and_run_next(ILLEGAL_INSTRUCTION_EXIT_CODE);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
-            suspend_current_and_run_next();
+            sample_cpu_profile();
+            if current_exceeds_time_budget() {
+                error!(
+                    "[kernel] Application exceeded its time budget of {} ms, exit code {}, last syscall {:?}.",
+                    APP_TIME_BUDGET_MS.unwrap(),
+                    TIME_BUDGET_EXIT_CODE,
+                    get_current_last_syscall()
+                );
+                exit_current_and_run_next(TIME_BUDGET_EXIT_CODE);
+            } else {
+                preempt_current_and_run_next();
+            }
         }
         _ => {
             panic!(
@@ -87,9 +125,20 @@ pub fn trap_handler() -> ! {
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    check_current_kernel_stack();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT;
     let user_satp = current_user_token();
+    #[cfg(debug_assertions)]
+    assert!(
+        current_satp_is_valid() && satp_is_valid(user_satp),
+        "returning to user with a dead page table, satp = {:#x}",
+        user_satp
+    );
+    debug_assert!(
+        current_trap_cx().returns_to_user(),
+        "sstatus.SPP is Supervisor, sret would run the app in S-mode"
+    );
     extern "C" {
         fn __alltraps();
         fn __restore();
@@ -108,8 +157,54 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
+/// Where traps taken in S-mode land, see `set_kernel_trap_entry`. An S-mode
+/// `ecall` only gets here if the SBI delegates it; normally it is an SBI call.
 pub fn trap_from_kernel() -> ! {
+    if is_supervisor_ecall(&scause::read()) {
+        panic!("[kernel] ecall from S-mode at sepc = {:#x}!", sepc::read());
+    }
     panic!("a trap from kernel!");
 }
 
+#[allow(unused)]
+/// a context with SPP set to Supervisor is caught by the guard in `trap_return`
+pub fn spp_guard_test() {
+    use riscv::register::sstatus::SPP;
+    let mut cx = TrapContext::app_init_context(0x1000, 0x2000, 0, 0, trap_handler as usize);
+    assert!(cx.returns_to_user());
+    cx.sstatus.set_spp(SPP::Supervisor);
+    assert!(!cx.returns_to_user());
+    info!("spp_guard_test passed!");
+}
+
+/// `scause` as it reads back after `bits` were written to it.
+fn scause_with(bits: usize) -> scause::Scause {
+    unsafe {
+        core::arch::asm!("csrw scause, {}", in(reg) bits);
+    }
+    scause::read()
+}
+
+#[allow(unused)]
+/// decoded from the `scause` register itself: only an exception with code 9
+/// counts as an S-mode ecall, not a user ecall nor the supervisor external
+/// interrupt sharing its code
+pub fn supervisor_ecall_test() {
+    let saved = scause::read().bits();
+    let interrupt = 1 << (usize::BITS - 1);
+    let ecall = scause_with(SUPERVISOR_ENV_CALL);
+    assert!(ecall.is_exception() && is_supervisor_ecall(&ecall));
+    let user_ecall = scause_with(8);
+    assert_eq!(user_ecall.cause(), Trap::Exception(Exception::UserEnvCall));
+    assert!(!is_supervisor_ecall(&user_ecall));
+    let external = scause_with(interrupt | SUPERVISOR_ENV_CALL);
+    assert_eq!(
+        external.cause(),
+        Trap::Interrupt(Interrupt::SupervisorExternal)
+    );
+    assert!(!is_supervisor_ecall(&external));
+    scause_with(saved);
+    info!("supervisor_ecall_test passed!");
+}
+
 pub use context::TrapContext;
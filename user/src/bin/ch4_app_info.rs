@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_app_info, AppInfo};
+
+/*
+理想结果：输出 Test app info OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut info = AppInfo::new();
+    assert_eq!(get_app_info(&mut info), 0);
+    // the loader names apps after their elf files
+    assert_eq!(info.name(), "ch4_app_info");
+    assert!(info.base <= info.entry && info.entry < info.base + info.size);
+    assert_eq!(info.base % 4096, 0);
+    assert_eq!(info.size % 4096, 0);
+    let here = main as usize;
+    assert!(info.base <= here && here < info.base + info.size);
+    println!("Test app info OK!");
+    0
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{clock_getres, mmap, munmap, TimeSpec};
+
+/*
+理想结果：输出 Test clock_getres OK!
+*/
+
+/// `CLOCK_FREQ` of the kernel on qemu virt
+const CLOCK_FREQ: usize = 12500000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut res = TimeSpec::new();
+    assert_eq!(0, clock_getres(&mut res));
+    assert_eq!(res.tv_sec, 0);
+    assert_eq!(res.tv_nsec, (1_000_000_000 + CLOCK_FREQ - 1) / CLOCK_FREQ);
+    // a TimeSpec straddling two pages is written in full
+    let start: usize = 0x10000000;
+    assert_eq!(0, mmap(start, 4096 * 2, 3));
+    let res2 = unsafe { &mut *((start + 4096 - 8) as *mut TimeSpec) };
+    assert_eq!(0, clock_getres(res2));
+    assert_eq!(res2.tv_sec, res.tv_sec);
+    assert_eq!(res2.tv_nsec, res.tv_nsec);
+    assert_eq!(0, munmap(start, 4096 * 2));
+    println!("Test clock_getres OK!");
+    0
+}
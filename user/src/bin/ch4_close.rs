@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{close, write};
+
+/*
+理想结果：输出 Test close OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(write(1, b""), 0);
+    assert_eq!(0, close(1));
+    // stdout is gone for this app only, stderr still works
+    assert_eq!(write(1, b"lost\n"), EINVAL);
+    assert_eq!(close(1), EINVAL);
+    assert_eq!(close(99), EINVAL);
+    assert_eq!(write(0, b"x"), EINVAL);
+    let msg = b"Test close OK!\n";
+    assert_eq!(write(2, msg), msg.len() as isize);
+    0
+}
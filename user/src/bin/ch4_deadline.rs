@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, set_deadline};
+
+/*
+理想结果：输出 Test set_deadline OK!，内核不报告错过截止时间
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(set_deadline(0), 0);
+    // far enough out to make it under any policy
+    assert_eq!(set_deadline(10_000), 0);
+    let start = get_time();
+    while get_time() - start < 50 {}
+    assert_eq!(set_deadline(0), 0);
+    println!("Test set_deadline OK!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::errno::{EINVAL, EMFILE};
+use user_lib::{close, dup, write};
+
+/*
+理想结果：输出 written through stdout
+written through the dup
+Test dup OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let fd = dup(1);
+    assert_eq!(fd, 3);
+    let msg = b"written through stdout\n";
+    assert_eq!(write(1, msg), msg.len() as isize);
+    let msg = b"written through the dup\n";
+    assert_eq!(write(fd as usize, msg), msg.len() as isize);
+    // the lowest free slot is taken first
+    assert_eq!(0, close(1));
+    assert_eq!(dup(fd as usize), 1);
+    assert_eq!(dup(42), EINVAL);
+    assert_eq!(dup(0), 4);
+    let mut last = 4;
+    loop {
+        match dup(2) {
+            EMFILE => break,
+            new_fd => {
+                assert_eq!(new_fd, last + 1);
+                last = new_fd;
+            }
+        }
+    }
+    assert!(last < 64);
+    assert_eq!(0, close(last as usize));
+    assert_eq!(dup(1), last);
+    let msg = b"Test dup OK!\n";
+    assert_eq!(write(1, msg), msg.len() as isize);
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EFAULT, EINVAL, ENOSYS};
+use user_lib::{mem_usage, mmap, munmap, read, syscall, write, MemUsage, SYSCALL_GETTIMEOFDAY};
+
+/*
+理想结果：输出 Test errno OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let unmapped: usize = 0x20000000;
+    assert_eq!(syscall(499, [0, 0, 0]), ENOSYS);
+    assert_eq!(write(42, b"x"), EINVAL);
+    assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [unmapped, 0, 0]), EFAULT);
+    let usage = unsafe { &mut *(unmapped as *mut MemUsage) };
+    assert_eq!(mem_usage(usage), EFAULT);
+    // fails before waiting for a key from stdin
+    let buf = unsafe { core::slice::from_raw_parts_mut(unmapped as *mut u8, 1) };
+    assert_eq!(read(0, buf), EFAULT);
+    // mmap and munmap keep the lab ABI of -1 for every failure
+    assert_eq!(mmap(unmapped + 1, 4096, 3), -1);
+    assert_eq!(munmap(unmapped, 4096), -1);
+    println!("Test errno OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{madvise, mmap_ex, MADV_DONTNEED, MMAP_LAZY};
+
+/*
+理想结果：输出 lazy code returned，
+然后再次执行被丢弃的代码页时因非法指令被杀死，内核不 panic。
+不输出 Should be killed 就算过。
+*/
+
+/// `ret`, that is `jalr x0, 0(ra)`
+const RET: u32 = 0x0000_8067;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    assert_eq!(start as isize, mmap_ex(start, len, 7, MMAP_LAZY));
+    let code: fn() = unsafe { core::mem::transmute(start) };
+    unsafe {
+        // the store faults the page in
+        (start as *mut u32).write_volatile(RET);
+        core::arch::asm!("fence.i");
+    }
+    code();
+    println!("lazy code returned");
+    // the next fetch is an instruction page fault, served with a zero page
+    assert_eq!(0, madvise(start, len, MADV_DONTNEED));
+    unsafe {
+        core::arch::asm!("fence.i");
+    }
+    code();
+    println!("Should be killed, Test exec_lazy fail!");
+    0
+}
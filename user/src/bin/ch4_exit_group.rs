@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit_group, yield_};
+
+/*
+理想结果：只与 ch4_exit_group_peer 一起运行 (在 os4 下 make exit-group-test)，
+输出 exit_group(7) with a peer still ready，内核报告 ch4_exit_group_peer 被结束，
+输出 [kernel] exit code 7，QEMU 以失败退出
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    // let the peer get going first
+    for _ in 0..10 {
+        yield_();
+    }
+    println!("exit_group(7) with a peer still ready");
+    exit_group(7);
+}
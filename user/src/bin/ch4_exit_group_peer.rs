@@ -0,0 +1,17 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::yield_;
+
+/*
+理想结果：不会自行退出，只能被 exit_group 结束
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    loop {
+        yield_();
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{free_frames, mmap, munmap};
+
+/*
+理想结果：输出 Test free frames OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    // the first mapping also allocates page-table frames, which stay around
+    assert_eq!(0, mmap(start, len, 3));
+    assert_eq!(0, munmap(start, len));
+    let before = free_frames();
+    assert!(before > 0);
+    assert_eq!(0, mmap(start, len, 3));
+    assert_eq!(free_frames(), before - 4);
+    assert_eq!(0, munmap(start, len));
+    assert_eq!(free_frames(), before);
+    println!("Test free frames OK!");
+    0
+}
@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getpid, getppid};
+
+/*
+理想结果：输出 Test getppid OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    // apps loaded at boot have no parent
+    assert!(getpid() >= 0);
+    assert_eq!(getppid(), -1);
+    println!("Test getppid OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::ENOSYS;
+use user_lib::{
+    getpid, syscall, task_stats, yield_, TaskStats, SYSCALL_GETPID, SYSCALL_TASK_STATS,
+    SYSCALL_YIELD,
+};
+
+/*
+理想结果：输出 Test last syscall OK!
+*/
+
+/// an id no syscall has
+const SYSCALL_UNKNOWN: usize = 4000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut stats = TaskStats::new();
+    assert_eq!(0, task_stats(&mut stats));
+    assert_eq!(stats.last_syscall, SYSCALL_TASK_STATS);
+    getpid();
+    yield_();
+    // counted as the last syscall too, although it has no counter
+    assert_eq!(syscall(SYSCALL_UNKNOWN, [0; 3]), ENOSYS);
+    assert_eq!(0, task_stats(&mut stats));
+    assert_eq!(stats.last_syscall, SYSCALL_TASK_STATS);
+    assert_eq!(stats.syscall_times[SYSCALL_GETPID], 1);
+    assert_eq!(stats.syscall_times[SYSCALL_YIELD], 1);
+    println!("Test last syscall OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{free_frames, madvise, mmap, mmap_ex, munmap, MADV_DONTNEED, MMAP_HUGE};
+
+/*
+理想结果：输出 Test madvise OK!
+*/
+
+const HUGE_PAGE_SIZE: usize = 0x20_0000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    assert_eq!(0, mmap(start, len, 3));
+    for i in (start..start + len).step_by(4096) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            *addr = 0xab;
+        }
+    }
+    let before = free_frames();
+    assert_eq!(0, madvise(start, len, MADV_DONTNEED));
+    assert_eq!(madvise(start, 0, MADV_DONTNEED), EINVAL);
+    assert_eq!(free_frames(), before + 4);
+    for i in (start..start + len).step_by(4096) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            assert_eq!(*addr, 0);
+            *addr = 0xcd;
+            assert_eq!(*addr, 0xcd);
+        }
+    }
+    assert_eq!(free_frames(), before);
+    // the range is still reserved, a second mmap over it fails
+    assert_eq!(mmap(start, 4096, 3), -1);
+    assert_eq!(0, munmap(start, len));
+    // a huge page cannot be faulted back in, so it is not discarded
+    let huge = mmap_ex(0x20000000, HUGE_PAGE_SIZE, 3, MMAP_HUGE);
+    assert_eq!(huge, 0x20000000);
+    let huge = huge as usize;
+    unsafe {
+        *(huge as *mut u8) = 0xab;
+    }
+    let before = free_frames();
+    assert_eq!(madvise(huge, HUGE_PAGE_SIZE, MADV_DONTNEED), EINVAL);
+    assert_eq!(madvise(huge + 4096, 4096, MADV_DONTNEED), EINVAL);
+    assert_eq!(free_frames(), before);
+    unsafe {
+        assert_eq!(*(huge as *const u8), 0xab);
+    }
+    assert_eq!(0, munmap(huge, HUGE_PAGE_SIZE));
+    println!("Test madvise OK!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{last_map_error, mmap, munmap};
+
+/*
+理想结果：输出 Test map conflict OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(last_map_error(), EINVAL);
+    assert_eq!(0, mmap(start, 0x3000, 3));
+    // the overlap is reported at its first page, not at the request start
+    assert_eq!(mmap(start - 0x1000, 0x2000, 3), -1);
+    assert_eq!(last_map_error(), start as isize);
+    assert_eq!(mmap(start + 0x2000, 0x2000, 3), -1);
+    assert_eq!(last_map_error(), (start + 0x2000) as isize);
+    // a later mmap that does not collide clears it
+    assert_eq!(0, mmap(start + 0x3000, 0x1000, 3));
+    assert_eq!(last_map_error(), EINVAL);
+    assert_eq!(0, munmap(start, 0x4000));
+    println!("Test map conflict OK!");
+    0
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{free_frames, kernel_meminfo, mmap, munmap, MemInfo};
+
+/*
+理想结果：输出 Test kernel meminfo OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut info = MemInfo::new();
+    assert_eq!(0, kernel_meminfo(&mut info));
+    assert!(info.heap_used > 0);
+    assert!(info.heap_used <= info.heap_peak);
+    assert!(info.frames_free <= info.frames_total);
+    assert_eq!(info.frames_free, free_frames() as usize);
+    // mapping pages shows up in the frame counts
+    let start: usize = 0x10000000;
+    assert_eq!(0, mmap(start, 4096 * 2, 3));
+    let mut after = MemInfo::new();
+    assert_eq!(0, kernel_meminfo(&mut after));
+    assert!(after.frames_free < info.frames_free);
+    assert_eq!(after.frames_total, info.frames_total);
+    assert!(after.heap_peak >= info.heap_peak);
+    assert_eq!(0, munmap(start, 4096 * 2));
+    println!("Test kernel meminfo OK!");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mincore, mmap_ex, munmap, MMAP_LAZY};
+
+/*
+理想结果：输出 Test mincore OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    assert_eq!(start as isize, mmap_ex(start, len, 3, MMAP_LAZY));
+    let mut vec = [0xffu8; 4];
+    assert_eq!(0, mincore(start, len, &mut vec));
+    assert_eq!(vec, [0, 0, 0, 0]);
+    // touch every other page
+    for i in (start..start + len).step_by(4096 * 2) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            *addr = 0xab;
+        }
+    }
+    assert_eq!(0, mincore(start, len, &mut vec));
+    assert_eq!(vec, [1, 0, 1, 0]);
+    // one page past the mapping is not reserved
+    let mut vec = [0u8; 5];
+    assert!(mincore(start, len + 4096, &mut vec) < 0);
+    assert!(mincore(start + 1, 4096, &mut vec) < 0);
+    assert_eq!(mincore(start, 0, &mut vec), -1);
+    assert_eq!(mincore(start, usize::MAX, &mut vec), -1);
+    assert_eq!(0, munmap(start, len));
+    assert!(mincore(start, 4096, &mut vec) < 0);
+    println!("Test mincore OK!");
+    0
+}
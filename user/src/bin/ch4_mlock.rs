@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EINVAL, ENOMEM};
+use user_lib::{mlock, mmap, munlock, munmap};
+
+/*
+理想结果：输出 Test mlock OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    assert_eq!(0, mmap(start, len, 3));
+    assert_eq!(0, mlock(start, 4096 * 2));
+    // locking twice and unlocking pages never locked are fine
+    assert_eq!(0, mlock(start, 4096));
+    assert_eq!(0, munlock(start, len));
+    assert_eq!(mlock(start + 1, 4096), EINVAL);
+    assert_eq!(mlock(start, 0), EINVAL);
+    assert_eq!(mlock(start, usize::MAX), EINVAL);
+    assert_eq!(mlock(usize::MAX - 2 * 4096 + 1, 4096), ENOMEM);
+    assert_eq!(mlock(start, len + 4096), ENOMEM);
+    assert_eq!(munlock(start + len, 4096), ENOMEM);
+    assert_eq!(0, mlock(start, len));
+    // locked pages can still be unmapped
+    assert_eq!(0, munmap(start, len));
+    assert_eq!(mlock(start, 4096), ENOMEM);
+    println!("Test mlock OK!");
+    0
+}
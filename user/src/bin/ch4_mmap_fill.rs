@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{free_frames, mmap_ex, mmap_filled, munmap, MMAP_FILL, MMAP_LAZY};
+
+/*
+理想结果：输出 Test mmap fill OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 2;
+    assert_eq!(mmap_filled(start, len, 3, 0xab), start as isize);
+    for i in start..start + len {
+        let addr: *const u8 = i as *const u8;
+        unsafe {
+            assert_eq!(*addr, 0xab);
+        }
+    }
+    assert_eq!(0, munmap(start, len));
+    // fill needs frames up front
+    assert_eq!(mmap_ex(start, len, 3, MMAP_LAZY | MMAP_FILL), EINVAL);
+    // a lazy map takes no frames until touched
+    let before = free_frames();
+    assert_eq!(mmap_ex(start, len, 3, MMAP_LAZY), start as isize);
+    assert_eq!(free_frames(), before);
+    unsafe {
+        assert_eq!(*(start as *const u8), 0);
+    }
+    assert_eq!(free_frames(), before - 1);
+    assert_eq!(0, munmap(start, len));
+    println!("Test mmap fill OK!");
+    0
+}
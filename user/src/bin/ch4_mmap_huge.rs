@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap_ex, munmap, MMAP_HUGE};
+
+/*
+理想结果：输出 Test mmap huge page OK!
+*/
+
+const HUGE_PAGE_SIZE: usize = 0x20_0000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start = mmap_ex(0x20000000, HUGE_PAGE_SIZE, 3, MMAP_HUGE);
+    assert_eq!(start, 0x20000000);
+    let start = start as usize;
+    for i in (start..start + HUGE_PAGE_SIZE).step_by(4096) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            assert_eq!(*addr, 0);
+            *addr = (i >> 12) as u8;
+        }
+    }
+    for i in (start..start + HUGE_PAGE_SIZE).step_by(4096) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            assert_eq!(*addr, (i >> 12) as u8);
+        }
+    }
+    // a huge page cannot be unmapped in part
+    assert_eq!(munmap(start, 4096), -1);
+    assert_eq!(0, munmap(start, HUGE_PAGE_SIZE));
+    println!("Test mmap huge page OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{mmap, mmap_ex, munmap, MMAP_HUGE_ALIGN};
+
+/*
+理想结果：输出 Test mmap huge align OK!
+*/
+
+const HUGE_PAGE_SIZE: usize = 0x20_0000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let hint: usize = 0x10001000;
+    let start = mmap_ex(hint, 4096, 3, MMAP_HUGE_ALIGN);
+    assert!(start > 0);
+    let start = start as usize;
+    assert_eq!(start % HUGE_PAGE_SIZE, 0);
+    assert!(start >= hint);
+    let last = (start + HUGE_PAGE_SIZE - 8) as *mut usize;
+    unsafe {
+        *(start as *mut usize) = 1;
+        *last = 2;
+        assert_eq!(*last, 2);
+    }
+    // the whole rounded range is taken
+    assert_eq!(mmap(start + HUGE_PAGE_SIZE - 4096, 4096, 3), -1);
+    assert!(mmap_ex(start, 4096, 3, MMAP_HUGE_ALIGN) < 0);
+    assert_eq!(0, munmap(start, HUGE_PAGE_SIZE));
+    // rounding these up would wrap around to a low address
+    assert_eq!(mmap_ex(usize::MAX - 4095, 4096, 3, MMAP_HUGE_ALIGN), EINVAL);
+    assert_eq!(mmap_ex(hint, usize::MAX - 4095, 3, MMAP_HUGE_ALIGN), EINVAL);
+    println!("Test mmap huge align OK!");
+    0
+}
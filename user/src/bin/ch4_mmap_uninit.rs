@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{get_time, mmap, mmap_ex, munmap, MMAP_UNINITIALIZED};
+
+/*
+理想结果：输出两次映射的耗时和 Test mmap uninitialized OK!，
+内核未开启 mmap_uninit 时输出 mmap uninitialized disabled
+*/
+
+/// large enough for zeroing to show up on a ms timer
+const LEN: usize = 16 << 20;
+
+#[no_mangle]
+fn main() -> i32 {
+    let zeroed: usize = 0x10000000;
+    let uninit: usize = zeroed + LEN;
+    let t0 = get_time();
+    assert_eq!(mmap(zeroed, LEN, 3), 0);
+    let t1 = get_time();
+    match mmap_ex(uninit, LEN, 3, MMAP_UNINITIALIZED) {
+        EINVAL => println!("mmap uninitialized disabled"),
+        ret => {
+            let t2 = get_time();
+            assert_eq!(ret, uninit as isize);
+            println!(
+                "zeroed map: {}ms, uninitialized map: {}ms",
+                t1 - t0,
+                t2 - t1
+            );
+            // written before read, as the flag requires
+            for page in (uninit..uninit + LEN).step_by(4096) {
+                let p = page as *mut usize;
+                unsafe {
+                    p.write_volatile(page);
+                    assert_eq!(p.read_volatile(), page);
+                }
+            }
+            assert_eq!(munmap(uninit, LEN), 0);
+            println!("Test mmap uninitialized OK!");
+        }
+    }
+    assert_eq!(munmap(zeroed, LEN), 0);
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EINVAL, ENOMEM};
+use user_lib::{mincore, mmap_ex, mprotect, munmap, MMAP_LAZY};
+
+/*
+理想结果：输出 Test mprotect OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 2;
+    assert_eq!(start as isize, mmap_ex(start, len, 3, MMAP_LAZY));
+    // downgrade before anything is faulted in, then fault in by reading
+    assert_eq!(0, mprotect(start, len, 1));
+    let addr = start as *mut u8;
+    unsafe {
+        assert_eq!(addr.read_volatile(), 0);
+    }
+    let mut vec = [0u8; 2];
+    assert_eq!(0, mincore(start, len, &mut vec));
+    assert_eq!(vec, [1, 0]);
+    // upgrade again, the resident page becomes writable
+    assert_eq!(0, mprotect(start, len, 3));
+    unsafe {
+        addr.write_volatile(0xab);
+        assert_eq!(addr.read_volatile(), 0xab);
+    }
+    assert_eq!(mprotect(start + 1, 4096, 1), EINVAL);
+    assert_eq!(mprotect(start, 4096, 0), EINVAL);
+    assert_eq!(mprotect(start, len + 4096, 1), ENOMEM);
+    assert_eq!(0, munmap(start, len));
+    println!("Test mprotect OK!");
+    0
+}
@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{getpid, notify};
+
+/*
+理想结果：输出 Test notify before pause OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let pid = getpid();
+    assert!(pid >= 0);
+    // nobody is waiting yet, the wakeup is dropped
+    assert_eq!(notify(pid as usize), EINVAL);
+    assert_eq!(notify(pid as usize), EINVAL);
+    // ch4_pause0/ch4_pause1 cover the notify that arrives after a pause
+    println!("Test notify before pause OK!");
+    0
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::pause;
+
+/*
+理想结果：与 ch4_pause1 一起运行，输出 Test pause OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    // stays blocked until ch4_pause1 notifies it
+    assert_eq!(pause(), 0);
+    println!("Test pause OK!");
+    0
+}
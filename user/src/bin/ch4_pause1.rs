@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::ESRCH;
+use user_lib::{notify, yield_};
+
+/*
+理想结果：唤醒 ch4_pause0，输出 Test notify OK!
+*/
+
+const MAX_PID: usize = 64;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(notify(MAX_PID * 16), ESRCH);
+    // the only blocked task is ch4_pause0, wait for it to pause
+    loop {
+        if (0..MAX_PID).any(|pid| notify(pid) == 0) {
+            break;
+        }
+        yield_();
+    }
+    println!("Test notify OK!");
+    0
+}
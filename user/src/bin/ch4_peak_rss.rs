@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_usage, mmap, munmap, MemUsage};
+
+/*
+理想结果：输出 Test peak rss OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    let mut before = MemUsage::new();
+    let mut grown = MemUsage::new();
+    let mut shrunk = MemUsage::new();
+    assert_eq!(0, mem_usage(&mut before));
+    assert_eq!(0, mmap(start, len, 3));
+    assert_eq!(0, mem_usage(&mut grown));
+    assert_eq!(grown.resident_pages, before.resident_pages + 4);
+    assert!(grown.peak_resident_pages >= grown.resident_pages);
+    assert_eq!(0, munmap(start, len));
+    assert_eq!(0, mem_usage(&mut shrunk));
+    assert_eq!(shrunk.resident_pages, before.resident_pages);
+    assert_eq!(shrunk.peak_resident_pages, grown.peak_resident_pages);
+    println!("Test peak rss OK!");
+    0
+}
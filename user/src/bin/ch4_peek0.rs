@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, mmap, munmap, yield_};
+
+/*
+理想结果：与 ch4_peek1 一起运行，输出 Test peek target OK!
+*/
+
+/// where ch4_peek1 looks for `MAGIC` in every task
+const PEEK_ADDR: usize = 0x2000_0000;
+static MAGIC: [u8; 16] = *b"peek_task magic!";
+/// how long `MAGIC` stays readable
+const HOLD_MS: isize = 3000;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(0, mmap(PEEK_ADDR, 4096, 3));
+    let page = unsafe { core::slice::from_raw_parts_mut(PEEK_ADDR as *mut u8, MAGIC.len()) };
+    page.copy_from_slice(&MAGIC);
+    let start = get_time();
+    while get_time() < start + HOLD_MS {
+        yield_();
+    }
+    assert_eq!(0, munmap(PEEK_ADDR, 4096));
+    println!("Test peek target OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EFAULT, ENOSYS, ESRCH};
+use user_lib::{getpid, peek_task, yield_};
+
+/*
+理想结果：在 ch4_peek0 的地址空间中读到 peek_task magic!，输出 Test peek_task OK!
+（内核需以 peek_task 特性编译，否则输出 peek_task disabled）
+*/
+
+const PEEK_ADDR: usize = 0x2000_0000;
+const MAGIC: &[u8; 16] = b"peek_task magic!";
+const MAX_PID: usize = 64;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0u8; 16];
+    let me = getpid() as usize;
+    match peek_task(me, PEEK_ADDR, &mut buf) {
+        ENOSYS => {
+            println!("peek_task disabled");
+            return 0;
+        }
+        // nothing mapped there in this task
+        ret => assert_eq!(ret, EFAULT),
+    }
+    assert_eq!(peek_task(MAX_PID * 16, PEEK_ADDR, &mut buf), ESRCH);
+    // ch4_peek0 maps MAGIC at PEEK_ADDR, find it among the tasks
+    let target = loop {
+        let found = (0..MAX_PID)
+            .filter(|&pid| pid != me)
+            .find(|&pid| peek_task(pid, PEEK_ADDR, &mut buf) == 16 && &buf == MAGIC);
+        match found {
+            Some(pid) => break pid,
+            None => {
+                yield_();
+            }
+        }
+    };
+    // the page ends 4096 bytes in, nothing past it is mapped
+    let mut tail = [0u8; 8];
+    assert_eq!(peek_task(target, PEEK_ADDR + 4092, &mut tail), EFAULT);
+    println!("Test peek_task OK!");
+    0
+}
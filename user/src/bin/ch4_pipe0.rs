@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EINVAL, EPIPE};
+use user_lib::{close, pipe, write, yield_};
+
+/*
+理想结果：与 ch4_pipe1 一起运行，输出 Test pipe write OK!
+*/
+
+/// more than the pipe holds, so the writer has to wait for the reader
+const LEN: usize = 1000;
+
+#[no_mangle]
+fn main() -> i32 {
+    // the first pipe created, id 0, which ch4_pipe1 opens
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+    assert_eq!(write(read_fd, b"x"), EINVAL);
+    // with our own read end closed, writes fail until ch4_pipe1 opens one
+    assert_eq!(close(read_fd), 0);
+    let mut data = [0u8; LEN];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let mut written = 0;
+    while written < LEN {
+        match write(write_fd, &data[written..]) {
+            EPIPE => {
+                yield_();
+            }
+            count => {
+                assert!(count > 0);
+                written += count as usize;
+            }
+        }
+    }
+    // the reader sees end of file once this is closed
+    assert_eq!(close(write_fd), 0);
+    println!("Test pipe write OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EFAULT, EINVAL};
+use user_lib::{pipe_open, read, yield_};
+
+/*
+理想结果：读出 ch4_pipe0 写入的 1000 个字节，输出 Test pipe read OK!
+*/
+
+const LEN: usize = 1000;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(pipe_open(0, 2), EINVAL);
+    // wait for ch4_pipe0 to create pipe 0
+    let fd = loop {
+        match pipe_open(0, 0) {
+            EINVAL => {
+                yield_();
+            }
+            fd => break fd as usize,
+        }
+    };
+    let mut buf = [0u8; 100];
+    let unmapped = unsafe { core::slice::from_raw_parts_mut(0x20000000 as *mut u8, 100) };
+    let mut total = 0;
+    loop {
+        // a bad buffer fails without taking bytes out of the pipe
+        assert_eq!(read(fd, unmapped), EFAULT);
+        let count = read(fd, &mut buf);
+        assert!(count >= 0);
+        if count == 0 {
+            break;
+        }
+        for &byte in &buf[..count as usize] {
+            assert_eq!(byte, (total % 251) as u8);
+            total += 1;
+        }
+    }
+    assert_eq!(total, LEN);
+    println!("Test pipe read OK!");
+    0
+}
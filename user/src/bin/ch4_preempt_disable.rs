@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, preempt_disable, preempt_enable};
+
+/*
+理想结果：输出 Test preempt disable OK!，内核报告一次强制抢占
+*/
+
+/// a few 10 ms ticks, well below the kernel's limit of 20
+const SHORT_MS: isize = 50;
+/// past the kernel's limit
+const LONG_MS: isize = 300;
+
+fn spin(ms: isize) {
+    let start = get_time();
+    while get_time() - start < ms {}
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // nothing held off without a disable
+    assert_eq!(preempt_enable(), 0);
+    assert_eq!(preempt_disable(), 0);
+    spin(SHORT_MS);
+    // the ticks were held off and the preemption comes now
+    assert!(preempt_enable() > 0);
+    assert_eq!(preempt_disable(), 0);
+    spin(LONG_MS);
+    // preempted by the limit on the way, enabled again since
+    assert_eq!(preempt_enable(), 0);
+    println!("Test preempt disable OK!");
+    0
+}
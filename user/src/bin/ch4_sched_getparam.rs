@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{sched_getparam, set_priority, SchedParam};
+
+/*
+理想结果：输出 Test sched_getparam OK!
+*/
+
+/// one time slice, 10 ms
+const TIME_SLICE_US: usize = 10_000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut param = SchedParam::new();
+    assert_eq!(sched_getparam(&mut param), 0);
+    assert_eq!(param.priority, 16);
+    assert!(param.time_slice_left <= TIME_SLICE_US);
+    assert!(param.policy <= 3);
+    assert_eq!(set_priority(42), 42);
+    assert_eq!(sched_getparam(&mut param), 0);
+    assert_eq!(param.priority, 42);
+    // a rejected priority leaves it as it was
+    assert_eq!(set_priority(1), -1);
+    assert_eq!(sched_getparam(&mut param), 0);
+    assert_eq!(param.priority, 42);
+    println!("Test sched_getparam OK!");
+    0
+}
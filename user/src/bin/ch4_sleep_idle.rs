@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, sleep_blocking};
+
+/*
+理想结果：输出 Test sleep idle OK!
+单独运行时所有任务都在睡眠，内核切换到 idle 任务等待，而不是直接结束
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start = get_time();
+    sleep_blocking(100);
+    let elapsed = get_time() - start;
+    assert!(elapsed >= 100);
+    println!("Test sleep idle OK!");
+    0
+}
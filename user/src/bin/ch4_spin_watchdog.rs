@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/*
+理想结果：内核日志出现 possible infinite loop 警告，随后输出 Test preempt watchdog OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    // spin long enough to be preempted past the kernel threshold, with no syscall
+    let mut counter: usize = 0;
+    for _ in 0..(1usize << 31) {
+        unsafe {
+            let value = core::ptr::read_volatile(&counter);
+            core::ptr::write_volatile(&mut counter, value.wrapping_add(1));
+        }
+    }
+    println!("Test preempt watchdog OK!");
+    0
+}
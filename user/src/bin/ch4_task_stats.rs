@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    get_time, mmap_ex, munmap, task_stats, yield_, TaskStats, MMAP_LAZY, SYSCALL_MMAP_EX,
+    SYSCALL_MUNMAP, SYSCALL_TASK_STATS, SYSCALL_YIELD,
+};
+
+/*
+理想结果：输出 Test task stats OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let t0 = get_time();
+    let mut before = TaskStats::new();
+    assert_eq!(0, task_stats(&mut before));
+    // known workload: fault in 3 lazy pages, yield 5 times, unmap
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 3;
+    assert_eq!(start as isize, mmap_ex(start, len, 3, MMAP_LAZY));
+    for i in (start..start + len).step_by(4096) {
+        let addr: *mut u8 = i as *mut u8;
+        unsafe {
+            *addr = 1;
+        }
+    }
+    for _ in 0..5 {
+        yield_();
+    }
+    let mut after = TaskStats::new();
+    assert_eq!(0, task_stats(&mut after));
+    assert_eq!(after.page_faults, before.page_faults + 3);
+    assert_eq!(after.resident_pages, before.resident_pages + 3);
+    assert!(after.peak_resident_pages >= after.resident_pages);
+    assert!(after.peak_resident_pages >= before.peak_resident_pages + 3);
+    // every yield switches out and back in
+    assert!(after.schedules >= before.schedules + 5);
+    assert!(after.run_time >= before.run_time);
+    assert!(after.run_time - before.run_time <= (get_time() - t0) as usize + 1);
+    assert_eq!(after.syscall_times[SYSCALL_TASK_STATS], 2);
+    assert_eq!(after.syscall_times[SYSCALL_MMAP_EX], 1);
+    assert_eq!(after.syscall_times[SYSCALL_YIELD], 5);
+    assert_eq!(0, munmap(start, len));
+    assert_eq!(0, task_stats(&mut after));
+    assert_eq!(after.resident_pages, before.resident_pages);
+    assert_eq!(after.syscall_times[SYSCALL_MUNMAP], 1);
+    println!("Test task stats OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{task_stats, TaskStats};
+
+/*
+理想结果：将内核 config 中的 APP_TIME_BUDGET_MS 设为小于 3000 的值（如 Some(1000)）时，
+内核输出 Application exceeded its time budget ... exit code -9，
+结束时报告本程序 Exited, exit code -9，不输出 spun for 3000ms。
+APP_TIME_BUDGET_MS 为 None 时输出 time budget off, spun for 3000ms
+*/
+
+/// running time to spin for, past any budget worth testing with
+const SPIN_MS: usize = 3000;
+
+#[no_mangle]
+fn main() -> i32 {
+    // counted in time actually spent running, like the budget
+    let mut stats = TaskStats::new();
+    loop {
+        assert_eq!(task_stats(&mut stats), 0);
+        if stats.run_time >= SPIN_MS {
+            break;
+        }
+    }
+    println!("time budget off, spun for {}ms", SPIN_MS);
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{madvise, munmap, MADV_DONTNEED};
+
+/*
+理想结果：输出 Test trap_cx guard OK!
+*/
+
+/// where the kernel keeps the TrapContext of every app
+const TRAP_CONTEXT: usize = usize::MAX - 2 * 4096 + 1;
+
+#[no_mangle]
+fn main() -> i32 {
+    // the page stays mapped, so the syscalls below still trap back in
+    assert_eq!(munmap(TRAP_CONTEXT, 4096), -1);
+    assert_eq!(munmap(TRAP_CONTEXT - 4096, 4096 * 2), -1);
+    assert_eq!(madvise(TRAP_CONTEXT, 4096, MADV_DONTNEED), EINVAL);
+    assert_eq!(madvise(TRAP_CONTEXT, usize::MAX, MADV_DONTNEED), EINVAL);
+    println!("Test trap_cx guard OK!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap};
+
+/*
+理想结果：输出 Test unmap length OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    assert_eq!(0, mmap(start, len * 2, 3));
+    // an end in the middle of the second page is refused, nothing is freed
+    assert_eq!(munmap(start, len + 1), -1);
+    assert_eq!(munmap(start, len * 2 - 1), -1);
+    unsafe {
+        *((start + len) as *mut u8) = 1;
+        *(start as *mut u8) = 1;
+    }
+    assert_eq!(munmap(start, len), 0);
+    assert_eq!(munmap(start, len), -1);
+    assert_eq!(munmap(start + len, len), 0);
+    println!("Test unmap length OK!");
+    0
+}
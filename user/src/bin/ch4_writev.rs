@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EFAULT, EINVAL};
+use user_lib::{writev, IoVec};
+
+/*
+理想结果：输出 Hello, writev!
+然后输出 Test writev OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let segments: [&[u8]; 3] = [b"Hello", b", ", b"writev!\n"];
+    let iov = segments.map(IoVec::new);
+    assert_eq!(writev(1, &iov), 15);
+    assert_eq!(writev(1, &[]), 0);
+    // a bad segment writes nothing at all
+    let bad = [
+        IoVec::new(b"not printed"),
+        IoVec {
+            base: 0x20000000,
+            len: 4,
+        },
+    ];
+    assert_eq!(writev(1, &bad), EFAULT);
+    // far more than the kernel could buffer, refused without copying
+    let huge = [IoVec {
+        base: 0x1000,
+        len: usize::MAX / 2,
+    }];
+    assert_eq!(writev(1, &huge), EFAULT);
+    // each segment ends in user space, but the total overflows
+    let overflow = [huge[0], huge[0], huge[0]];
+    assert_eq!(writev(1, &overflow), EINVAL);
+    assert_eq!(writev(42, &iov), EINVAL);
+    println!("Test writev OK!");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{mmap, mprotect, munmap};
+
+/*
+理想结果：输出 Test zero length OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    assert_eq!(mmap(start, 0, 3), -1);
+    assert_eq!(0, mmap(start, len, 3));
+    // an empty range is refused even inside a mapping
+    assert_eq!(munmap(start, 0), -1);
+    assert_eq!(mprotect(start, 0, 1), EINVAL);
+    assert_eq!(0, munmap(start, len));
+    println!("Test zero length OK!");
+    0
+}
@@ -0,0 +1,10 @@
+//! Error numbers returned by the kernel, negated like in Linux
+
+pub const ESRCH: isize = -3;
+pub const ENOMEM: isize = -12;
+pub const EFAULT: isize = -14;
+pub const EEXIST: isize = -17;
+pub const EINVAL: isize = -22;
+pub const EMFILE: isize = -24;
+pub const EPIPE: isize = -32;
+pub const ENOSYS: isize = -38;
@@ -5,6 +5,7 @@
 
 #[macro_use]
 pub mod console;
+pub mod errno;
 mod lang_items;
 mod syscall;
 
@@ -98,6 +99,19 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
@@ -131,6 +145,131 @@ impl TaskInfo {
     }
 }
 
+/// one segment passed to `writev`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+impl IoVec {
+    pub fn new(buf: &[u8]) -> Self {
+        IoVec {
+            base: buf.as_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct TaskStats {
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    pub page_faults: usize,
+    /// ms spent running
+    pub run_time: usize,
+    pub resident_pages: usize,
+    pub peak_resident_pages: usize,
+    pub schedules: usize,
+    /// id of the most recent syscall, the `task_stats` call reporting it
+    pub last_syscall: usize,
+}
+
+impl TaskStats {
+    pub fn new() -> Self {
+        TaskStats {
+            syscall_times: [0; MAX_SYSCALL_NUM],
+            page_faults: 0,
+            run_time: 0,
+            resident_pages: 0,
+            peak_resident_pages: 0,
+            schedules: 0,
+            last_syscall: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SchedParam {
+    pub priority: isize,
+    pub stride: usize,
+    /// us until the current time slice ends
+    pub time_slice_left: usize,
+    /// 0 round robin, 1 priority, 2 stride, 3 earliest deadline first
+    pub policy: usize,
+}
+
+impl SchedParam {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// longest app name `AppInfo` holds
+pub const APP_NAME_LEN: usize = 32;
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct AppInfo {
+    /// NUL padded
+    pub name: [u8; APP_NAME_LEN],
+    pub entry: usize,
+    /// lowest page of the loaded image
+    pub base: usize,
+    /// bytes from `base` to the end of the image
+    pub size: usize,
+}
+
+impl AppInfo {
+    pub fn new() -> Self {
+        Self {
+            name: [0; APP_NAME_LEN],
+            entry: 0,
+            base: 0,
+            size: 0,
+        }
+    }
+    /// the name up to its padding
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(APP_NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct MemUsage {
+    pub resident_pages: usize,
+    pub peak_resident_pages: usize,
+}
+
+impl MemUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct MemInfo {
+    pub heap_used: usize,
+    pub heap_peak: usize,
+    pub frames_free: usize,
+    pub frames_total: usize,
+}
+
+impl MemInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -195,6 +334,11 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+/// write every segment of `iov` in order, returning the total length
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
 pub fn link(old_path: &str, new_path: &str) -> isize {
     sys_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
 }
@@ -220,6 +364,12 @@ pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
 
+/// end every app, not just this one, and stop the kernel with `exit_code`
+pub fn exit_group(exit_code: i32) -> ! {
+    console::flush();
+    sys_exit_group(exit_code);
+}
+
 pub fn yield_() -> isize {
     sys_yield()
 }
@@ -232,10 +382,20 @@ pub fn get_time() -> isize {
     }
 }
 
+/// resolution of the clock behind `get_time`
+pub fn clock_getres(res: &mut TimeSpec) -> isize {
+    sys_clock_getres(res)
+}
+
 pub fn getpid() -> isize {
     sys_getpid()
 }
 
+/// id of the parent task, -1 if there is none
+pub fn getppid() -> isize {
+    sys_getppid()
+}
+
 pub fn fork() -> isize {
     sys_fork()
 }
@@ -248,6 +408,27 @@ pub fn set_priority(prio: isize) -> isize {
     sys_set_priority(prio)
 }
 
+/// hold off timer preemption for a short critical section
+pub fn preempt_disable() -> isize {
+    sys_preempt_disable()
+}
+
+/// allow timer preemption again, returning the ticks held off; a held off
+/// preemption happens right away
+pub fn preempt_enable() -> isize {
+    sys_preempt_enable()
+}
+
+/// ask to be done within `ms` from now, 0 for no deadline
+pub fn set_deadline(ms: usize) -> isize {
+    sys_set_deadline(ms)
+}
+
+/// scheduling state of the current task
+pub fn sched_getparam(param: &mut SchedParam) -> isize {
+    sys_sched_getparam(param)
+}
+
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(-1, exit_code as *mut _) {
@@ -288,10 +469,41 @@ pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
     sys_mmap(start, len, prot)
 }
 
+/// address of the first page in the way of the last mmap, `EINVAL` if it
+/// did not fail on an overlap
+pub fn last_map_error() -> isize {
+    sys_last_map_error()
+}
+
 pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+
+/// pin pages so the kernel will not evict them
+pub fn mlock(start: usize, len: usize) -> isize {
+    sys_mlock(start, len)
+}
+
+pub fn munlock(start: usize, len: usize) -> isize {
+    sys_munlock(start, len)
+}
+
+/// Fill `vec` with one byte per page of `[start, start + len)`, 1 if resident.
+pub fn mincore(start: usize, len: usize, vec: &mut [u8]) -> isize {
+    sys_mincore(start, len, vec)
+}
+
+/// `madvise` advice: drop resident pages, refault them as zero pages
+pub const MADV_DONTNEED: usize = 4;
+
+pub fn madvise(start: usize, len: usize, advice: usize) -> isize {
+    sys_madvise(start, len, advice)
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
@@ -302,11 +514,70 @@ pub fn dup(fd: usize) -> isize {
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
     sys_pipe(pipe_fd)
 }
+/// open the read (`end` 0) or write (`end` 1) end of pipe `id` created by any task
+pub fn pipe_open(id: usize, end: usize) -> isize {
+    sys_pipe_open(id, end)
+}
+/// read `buf.len()` bytes at `addr` in task `pid`, needs a kernel built with `peek_task`
+pub fn peek_task(pid: usize, addr: usize, buf: &mut [u8]) -> isize {
+    sys_peek_task(pid, addr, buf)
+}
 
 pub fn task_info(info: &TaskInfo) -> isize {
     sys_task_info(info)
 }
 
+/// every counter of the current task, `task_info` and `mem_usage` in one
+pub fn task_stats(stats: &mut TaskStats) -> isize {
+    sys_task_stats(stats)
+}
+
+/// name, entry point and loaded image of the current app
+pub fn get_app_info(info: &mut AppInfo) -> isize {
+    sys_get_app_info(info)
+}
+
+pub fn mem_usage(usage: &mut MemUsage) -> isize {
+    sys_mem_usage(usage)
+}
+
+pub fn free_frames() -> isize {
+    sys_free_frames()
+}
+
+/// `mmap_ex` flag: round start and length to 2MB boundaries
+pub const MMAP_HUGE_ALIGN: usize = 1 << 0;
+/// `mmap_ex` flag: back the range with 2MB huge pages, implies `MMAP_HUGE_ALIGN`
+pub const MMAP_HUGE: usize = 1 << 1;
+/// `mmap_ex` flag: only reserve the range, pages are faulted in on first access
+pub const MMAP_LAZY: usize = 1 << 2;
+/// `mmap_ex` flag: fill the new pages with a byte instead of zero, eager maps only
+pub const MMAP_FILL: usize = 1 << 3;
+/// `mmap_ex` flag: do not zero the new pages, eager maps only. They may hold
+/// other tasks' data; write before reading. Refused with `EINVAL` unless the
+/// kernel is built with `mmap_uninit`.
+pub const MMAP_UNINITIALIZED: usize = 1 << 4;
+
+pub fn mmap_ex(start: usize, len: usize, prot: usize, flags: usize) -> isize {
+    sys_mmap_ex(start, len, prot, flags, 0)
+}
+
+pub fn mmap_filled(start: usize, len: usize, prot: usize, fill: u8) -> isize {
+    sys_mmap_ex(start, len, prot, MMAP_FILL, fill as usize)
+}
+
+pub fn pause() -> isize {
+    sys_pause()
+}
+
+pub fn notify(pid: usize) -> isize {
+    sys_notify(pid)
+}
+
+pub fn kernel_meminfo(info: &mut MemInfo) -> isize {
+    sys_kernel_meminfo(info)
+}
+
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
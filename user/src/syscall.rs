@@ -1,19 +1,23 @@
-use crate::TaskInfo;
+use crate::{AppInfo, MemInfo, MemUsage, SchedParam, TaskInfo, TaskStats};
 
-use super::{Stat, TimeVal};
+use super::{IoVec, Stat, TimeSpec, TimeVal};
 
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_WRITEV: usize = 66;
 pub const SYSCALL_UNLINKAT: usize = 35;
 pub const SYSCALL_LINKAT: usize = 37;
 pub const SYSCALL_FSTAT: usize = 80;
 pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_EXIT_GROUP: usize = 94;
 pub const SYSCALL_SLEEP: usize = 101;
+pub const SYSCALL_SCHED_GETPARAM: usize = 121;
 pub const SYSCALL_YIELD: usize = 124;
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GETPPID: usize = 173;
 pub const SYSCALL_GETTID: usize = 178;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
@@ -21,12 +25,32 @@ pub const SYSCALL_WAITPID: usize = 260;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
+pub const SYSCALL_MLOCK: usize = 228;
+pub const SYSCALL_MUNLOCK: usize = 229;
+pub const SYSCALL_MINCORE: usize = 232;
+pub const SYSCALL_MADVISE: usize = 233;
 pub const SYSCALL_SPAWN: usize = 400;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
 pub const SYSCALL_PIPE: usize = 59;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_MEM_USAGE: usize = 411;
+pub const SYSCALL_FREE_FRAMES: usize = 412;
+pub const SYSCALL_MMAP_EX: usize = 413;
+pub const SYSCALL_PAUSE: usize = 414;
+pub const SYSCALL_NOTIFY: usize = 415;
+pub const SYSCALL_KERNEL_MEMINFO: usize = 416;
+pub const SYSCALL_CLOCK_GETRES: usize = 417;
+pub const SYSCALL_TASK_STATS: usize = 418;
+pub const SYSCALL_PIPE_OPEN: usize = 419;
+pub const SYSCALL_PEEK_TASK: usize = 420;
+pub const SYSCALL_SET_DEADLINE: usize = 421;
+pub const SYSCALL_LAST_MAP_ERROR: usize = 422;
+pub const SYSCALL_GET_APP_INFO: usize = 423;
+pub const SYSCALL_PREEMPT_DISABLE: usize = 424;
+pub const SYSCALL_PREEMPT_ENABLE: usize = 425;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -99,6 +123,10 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -146,6 +174,11 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("sys_exit never returns!");
 }
 
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT_GROUP, [exit_code as usize, 0, 0]);
+    panic!("sys_exit_group never returns!");
+}
+
 pub fn sys_sleep(sleep_ms: usize) -> isize {
     syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
 }
@@ -158,10 +191,18 @@ pub fn sys_get_time(time: &TimeVal, tz: usize) -> isize {
     syscall(SYSCALL_GETTIMEOFDAY, [time as *const _ as usize, tz, 0])
 }
 
+pub fn sys_clock_getres(res: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETRES, [res as *mut _ as usize, 0, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0, 0, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
@@ -181,6 +222,26 @@ pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
+pub fn sys_last_map_error() -> isize {
+    syscall(SYSCALL_LAST_MAP_ERROR, [0, 0, 0])
+}
+
+pub fn sys_preempt_disable() -> isize {
+    syscall(SYSCALL_PREEMPT_DISABLE, [0, 0, 0])
+}
+
+pub fn sys_preempt_enable() -> isize {
+    syscall(SYSCALL_PREEMPT_ENABLE, [0, 0, 0])
+}
+
+pub fn sys_set_deadline(ms: usize) -> isize {
+    syscall(SYSCALL_SET_DEADLINE, [ms, 0, 0])
+}
+
+pub fn sys_sched_getparam(param: &mut SchedParam) -> isize {
+    syscall(SYSCALL_SCHED_GETPARAM, [param as *mut _ as usize, 0, 0])
+}
+
 pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
     syscall(SYSCALL_MMAP, [start, len, prot])
 }
@@ -189,6 +250,26 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [start, len, prot])
+}
+
+pub fn sys_mlock(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MLOCK, [start, len, 0])
+}
+
+pub fn sys_munlock(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNLOCK, [start, len, 0])
+}
+
+pub fn sys_mincore(start: usize, len: usize, vec: &mut [u8]) -> isize {
+    syscall(SYSCALL_MINCORE, [start, len, vec.as_mut_ptr() as usize])
+}
+
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [start, len, advice])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
@@ -201,10 +282,53 @@ pub fn sys_pipe(pipe: &mut [usize]) -> isize {
     syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
 }
 
+pub fn sys_pipe_open(id: usize, end: usize) -> isize {
+    syscall(SYSCALL_PIPE_OPEN, [id, end, 0])
+}
+
+pub fn sys_peek_task(pid: usize, addr: usize, buf: &mut [u8]) -> isize {
+    syscall6(
+        SYSCALL_PEEK_TASK,
+        [pid, addr, buf.as_mut_ptr() as usize, buf.len(), 0, 0],
+    )
+}
+
 pub fn sys_task_info(info: &TaskInfo) -> isize {
     syscall(SYSCALL_TASK_INFO, [info as *const _ as usize, 0, 0])
 }
 
+pub fn sys_get_app_info(info: &mut AppInfo) -> isize {
+    syscall(SYSCALL_GET_APP_INFO, [info as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_task_stats(stats: &mut TaskStats) -> isize {
+    syscall(SYSCALL_TASK_STATS, [stats as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_mem_usage(usage: &mut MemUsage) -> isize {
+    syscall(SYSCALL_MEM_USAGE, [usage as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_free_frames() -> isize {
+    syscall(SYSCALL_FREE_FRAMES, [0, 0, 0])
+}
+
+pub fn sys_mmap_ex(start: usize, len: usize, prot: usize, flags: usize, fill: usize) -> isize {
+    syscall6(SYSCALL_MMAP_EX, [start, len, prot, flags, fill, 0])
+}
+
+pub fn sys_pause() -> isize {
+    syscall(SYSCALL_PAUSE, [0, 0, 0])
+}
+
+pub fn sys_notify(pid: usize) -> isize {
+    syscall(SYSCALL_NOTIFY, [pid, 0, 0])
+}
+
+pub fn sys_kernel_meminfo(info: &mut MemInfo) -> isize {
+    syscall(SYSCALL_KERNEL_MEMINFO, [info as *mut _ as usize, 0, 0])
+}
+
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
 }